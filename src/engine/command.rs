@@ -1,4 +1,4 @@
-use crate::language::Storage;
+use crate::language::{Construct, Storage};
 use crate::tree::Node;
 
 #[derive(Debug)]
@@ -34,6 +34,35 @@ pub enum TreeEdCommand {
     /// In a listy sequence, delete the node at the cursor and move the cursor to the right. In a
     /// fixed sequence, replace the node at the cursor with a hole.
     Delete,
+    /// Replace the node at the cursor with a new node of the given construct, and put the old node
+    /// in the new node's first child slot that accepts it (and is empty). Move the cursor to the
+    /// new node.
+    Wrap(Construct),
+    /// Replace the parent of the node at the cursor with the node at the cursor, discarding the
+    /// parent's other children. The cursor stays on the same node.
+    Raise,
+    /// Replace the node at the cursor with its first child that can take its place, discarding its
+    /// other children. Move the cursor to that child.
+    Unwrap,
+    /// The inverse of `Raise`: replace the node at the cursor with the given node, and put the node
+    /// that was at the cursor into the given node's i'th child slot. In a fixed sequence, that slot
+    /// must contain a hole. Move the cursor to the given node.
+    WrapIn(Node, usize),
+    /// In a listy sequence, swap the node at the cursor with its left sibling. The cursor follows
+    /// the node.
+    MoveLeft,
+    /// In a listy sequence, swap the node at the cursor with its right sibling. The cursor follows
+    /// the node.
+    MoveRight,
+    /// In a listy sequence, move the node at the cursor to be the first of its siblings. The cursor
+    /// follows the node.
+    MoveFirst,
+    /// In a listy sequence, move the node at the cursor to be the last of its siblings. The cursor
+    /// follows the node.
+    MoveLast,
+    /// In a listy sequence, move the node at the cursor to the given index among its siblings. The
+    /// cursor follows the node.
+    MoveTo(usize),
 }
 
 #[derive(Debug)]
@@ -50,17 +79,25 @@ pub enum TextEdCommand {
 // TODO: cut=copy,backspace  paste-copy=dup,paste
 #[derive(Debug)]
 pub enum ClipboardCommand {
-    /// Copy the node at the cursor and push it onto the clipboard stack.
+    /// Copy the node at the cursor (or the selected nodes) and push it onto the clipboard stack
+    /// as a single clip.
     Copy,
-    /// Pop the top node from the clipboard stack, and insert it at the cursor (in the same manner
-    /// as [`TreeEdCommand::Insert`]).
+    /// Pop the top clip from the clipboard stack, and insert its nodes at the cursor (in the same
+    /// manner as [`TreeEdCommand::Insert`]). If there is a selection, it gets replaced.
     Paste,
-    /// Swap the top node in the clipboard stack with the node at the cursor.
+    /// Swap the top clip in the clipboard stack with the node at the cursor. The clip must be a
+    /// single node.
     PasteSwap,
-    /// Duplicate the top node in the clipboard stack.
+    /// Duplicate the top clip in the clipboard stack.
     Dup,
-    /// Discard the top node in the clipboard stack.
+    /// Discard the top clip in the clipboard stack.
     Pop,
+    /// Copy the node at the cursor (or the selected nodes) into the given register, replacing its
+    /// previous contents.
+    CopyToRegister(char),
+    /// Insert a copy of the given register's contents at the cursor (in the same manner as
+    /// `Paste`). The register keeps its contents.
+    PasteFromRegister(char),
 }
 
 // TODO: First set of user nav commands to try: down-left & down-right
@@ -91,6 +128,10 @@ pub enum TreeNavCommand {
     NextText,
     /// Move the cursor to the previous texty node.
     PrevText,
+    /// Move the cursor to the next hole, wrapping around to the start of the document.
+    NextHole,
+    /// Move the cursor to the previous hole, wrapping around to the end of the document.
+    PrevHole,
     /// If the node at the cursor is texty, enter text mode, placing the cursor at the
     /// end of the text.
     EnterText,
@@ -136,8 +177,9 @@ impl TreeEdCommand {
         use TreeEdCommand::*;
 
         match self {
-            Insert(node) | Replace(node) => node.delete_root(s),
-            Backspace | Delete => (),
+            Insert(node) | Replace(node) | WrapIn(node, _) => node.delete_root(s),
+            Backspace | Delete | Wrap(_) | Raise | Unwrap => (),
+            MoveLeft | MoveRight | MoveFirst | MoveLast | MoveTo(_) => (),
         }
     }
 }
@@ -2,15 +2,19 @@ use super::command::{
     BookmarkCommand, ClipboardCommand, Command, EdCommand, NavCommand, TextEdCommand,
     TextNavCommand, TreeEdCommand, TreeNavCommand,
 };
-use crate::language::Storage;
+use super::line_map::LineMap;
+use crate::language::{Arity, Construct, Storage};
 use crate::pretty_doc::DocRef;
 use crate::tree::{Bookmark, Location, Mode, Node};
-use crate::util::{bug_assert, error, SynlessBug, SynlessError};
+use crate::util::{bug, bug_assert, error, SynlessBug, SynlessError};
 use std::collections::HashMap;
 
 /// A set of changes that can be undone/redone all at once.
 #[derive(Debug)]
 pub struct UndoGroup {
+    /// Identifies the state of the document after this group is executed. Kept when the group is
+    /// undone and redone.
+    id: usize,
     /// The position of the cursor before the first command was executed (where it should be
     /// restored to after undo-ing).
     restore_loc: Location,
@@ -38,8 +42,14 @@ pub enum EditError {
     CannotPlaceNode,
     #[error("No node to act on here")]
     NoNodeHere,
+    #[error("None of this node's children can take its place")]
+    NoReplacementChild,
     #[error("Clipboard is empty")]
     EmptyClipboard,
+    #[error("Register '{0}' is empty")]
+    EmptyRegister(char),
+    #[error("Can only select nodes in a list")]
+    CannotSelect,
 }
 
 impl From<EditError> for SynlessError {
@@ -55,6 +65,21 @@ pub struct Doc {
     undo_stack: Vec<UndoGroup>,
     redo_stack: Vec<UndoGroup>,
     bookmarks: HashMap<char, Bookmark>,
+    /// Used to give each new undo group a unique id.
+    next_undo_group_id: usize,
+    /// The version of the document (see `.version()`) when it was last saved, or `None` if no
+    /// version of it has been saved.
+    saved_version: Option<usize>,
+    /// Whether the source this doc was loaded from ended with a newline, so that saving it can
+    /// preserve that.
+    ends_with_newline: bool,
+    /// The node where the current selection started, if any. The selection extends from it to
+    /// the node at the cursor.
+    selection_anchor: Option<Node>,
+    /// The number of times this doc has been edited (not counting undo and redo).
+    edit_count: usize,
+    /// The line each node was on when the doc's source was last printed (e.g. when saving).
+    line_map: LineMap,
 }
 
 impl Doc {
@@ -70,6 +95,12 @@ impl Doc {
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             bookmarks: HashMap::new(),
+            next_undo_group_id: 1,
+            saved_version: Some(0),
+            ends_with_newline: false,
+            selection_anchor: None,
+            edit_count: 0,
+            line_map: LineMap::default(),
         })
     }
 
@@ -83,49 +114,206 @@ impl Doc {
     }
 
     pub fn doc_ref_display<'d>(&self, s: &'d Storage, highlight_cursor: bool) -> DocRef<'d> {
-        let opt_cursor = if highlight_cursor {
-            Some(self.cursor)
-        } else {
-            None
-        };
-        DocRef::new_display(s, opt_cursor, self.cursor.root_node(s))
+        if !highlight_cursor {
+            return DocRef::new_display(s, None, self.cursor.root_node(s));
+        }
+        let doc_ref = DocRef::new_display(s, Some(self.cursor), self.cursor.root_node(s));
+        match self.selection(s) {
+            Some((first, last)) => doc_ref.with_selection(first, last),
+            None => doc_ref,
+        }
+    }
+
+    /// Like `doc_ref_display`, but highlight the given cursor position instead of this doc's own
+    /// cursor. Used to show a second view of the same doc.
+    pub fn doc_ref_display_at<'d>(&self, s: &'d Storage, cursor: Location) -> DocRef<'d> {
+        DocRef::new_display(s, Some(cursor), self.cursor.root_node(s))
+    }
+
+    pub fn ends_with_newline(&self) -> bool {
+        self.ends_with_newline
+    }
+
+    pub fn set_ends_with_newline(&mut self, ends_with_newline: bool) {
+        self.ends_with_newline = ends_with_newline;
+    }
+
+    pub fn line_map(&self) -> &LineMap {
+        &self.line_map
+    }
+
+    pub fn set_line_map(&mut self, line_map: LineMap) {
+        self.line_map = line_map;
     }
 
     pub fn cursor(&self) -> Location {
         self.cursor
     }
 
+    pub fn edit_count(&self) -> usize {
+        self.edit_count
+    }
+
+    /// Start selecting a range of siblings, anchored at the node at the cursor. Moving the cursor
+    /// among that node's siblings extends the selection.
+    pub fn start_selection(&mut self, s: &Storage) -> Result<(), EditError> {
+        let node = self.cursor.node(s).ok_or(EditError::NoNodeHere)?;
+        match node.parent(s).map(|parent| parent.arity(s)) {
+            Some(Arity::Listy(_)) => {
+                self.selection_anchor = Some(node);
+                Ok(())
+            }
+            _ => Err(EditError::CannotSelect),
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// The first and last nodes of the selected range of siblings. Returns `None` if there is no
+    /// selection, or if the cursor has moved away from the anchor's siblings.
+    pub fn selection(&self, s: &Storage) -> Option<(Node, Node)> {
+        let anchor = self.selection_anchor?;
+        let node = self.cursor.node(s)?;
+        if !anchor.is_valid(s) || anchor.parent(s)? != node.parent(s)? {
+            return None;
+        }
+        if anchor.sibling_index(s) <= node.sibling_index(s) {
+            Some((anchor, node))
+        } else {
+            Some((node, anchor))
+        }
+    }
+
+    /// Get the location of a bookmark in this doc. Falls back to the start of the doc if the
+    /// bookmark's node has been deleted.
+    pub fn resolve_bookmark(&self, s: &Storage, mark: Bookmark) -> Location {
+        self.cursor.validate_bookmark(s, mark).unwrap_or_else(|| {
+            Location::before_children(s, self.cursor.root_node(s))
+                .bug_msg("Root constructs must be able to have at least 1 child")
+        })
+    }
+
+    /// Move the cursor to a bookmark, or to the start of the doc if the bookmark's node has been
+    /// deleted.
+    pub fn goto_bookmark(&mut self, s: &Storage, mark: Bookmark) {
+        self.cursor = self.resolve_bookmark(s, mark);
+    }
+
+    /// Move the cursor to a location elsewhere in the doc, such as a search result.
+    pub fn jump_to(&mut self, loc: Location) {
+        self.cursor = loc;
+    }
+
     pub fn mode(&self) -> Mode {
         self.cursor.mode()
     }
 
+    /// Whether the document differs from when it was last saved (or loaded). Undoing back to the
+    /// saved state makes it unmodified again.
+    pub fn is_modified(&self) -> bool {
+        self.saved_version.is_none() || self.version() != self.saved_version
+    }
+
+    /// Record that the current state of the document has been saved.
+    pub fn mark_saved(&mut self) {
+        self.end_undo_group();
+        self.saved_version = self.version();
+    }
+
+    /// Record that no version of the document has been saved, e.g. because it was restored from a
+    /// recovery file.
+    pub fn mark_unsaved(&mut self) {
+        self.saved_version = None;
+    }
+
+    /// Identifies the current state of the document, in terms of which edits have been made to
+    /// it. Is `None` while there are recent edits that haven't been grouped yet.
+    fn version(&self) -> Option<usize> {
+        if self.recent.is_some() {
+            None
+        } else {
+            Some(self.undo_stack.last().map(|group| group.id).unwrap_or(0))
+        }
+    }
+
     /// Executes a single command. Clears the redo stack if it was an editing command (but not if
-    /// it was a navigation command).
+    /// it was a navigation command). If there is a selection, tree editing and clipboard commands
+    /// act on the whole selected range, and editing commands end the selection.
     pub fn execute(
         &mut self,
         s: &mut Storage,
         cmd: Command,
-        clipboard: &mut Vec<Node>,
+        clipboard: &mut Vec<Vec<Node>>,
+        registers: &mut HashMap<char, Vec<Node>>,
     ) -> Result<(), EditError> {
         let restore_loc = self.cursor;
-        let undos = match cmd {
-            Command::Ed(cmd) => execute_ed(s, cmd, &mut self.cursor)?,
-            Command::Clipboard(cmd) => execute_clipboard(s, cmd, &mut self.cursor, clipboard)?,
-            Command::Nav(cmd) => {
+        let selection = self.selection(s);
+        let undos = match (cmd, selection) {
+            (Command::Ed(EdCommand::Tree(cmd)), Some(range)) => {
+                execute_tree_ed_on_range(s, cmd, &mut self.cursor, range)?
+            }
+            (Command::Ed(cmd), _) => execute_ed(s, cmd, &mut self.cursor)?,
+            (Command::Clipboard(cmd), _) => {
+                execute_clipboard(s, cmd, &mut self.cursor, clipboard, registers, selection)?
+            }
+            (Command::Nav(cmd), _) => {
                 execute_nav(s, cmd, &mut self.cursor, &mut self.bookmarks)?;
                 Vec::new()
             }
         };
+        self.record_undos(s, restore_loc, undos);
+        Ok(())
+    }
+
+    /// Replace each `old` node with its `new` node, as part of the current undo group. If any of
+    /// the `new` nodes isn't allowed where its `old` node is, does nothing (besides deleting the
+    /// `new` nodes) and returns an error.
+    pub fn replace_nodes(
+        &mut self,
+        s: &mut Storage,
+        replacements: Vec<(Node, Node)>,
+    ) -> Result<(), EditError> {
+        if replacements
+            .iter()
+            .any(|(old, new)| old.is_root(s) || !old.accepts_replacement(s, *new))
+        {
+            for (_, new) in replacements {
+                new.delete_root(s);
+            }
+            return Err(EditError::CannotPlaceNode);
+        }
+        let restore_loc = self.cursor;
+        let mut undos = Vec::new();
+        for (old, new) in replacements {
+            self.cursor = Location::at(s, old);
+            let cmd = EdCommand::Tree(TreeEdCommand::Replace(new));
+            undos.extend(execute_ed(s, cmd, &mut self.cursor).bug());
+        }
+        self.record_undos(s, restore_loc, undos);
+        Ok(())
+    }
+
+    fn record_undos(
+        &mut self,
+        s: &mut Storage,
+        restore_loc: Location,
+        undos: Vec<(Location, EdCommand)>,
+    ) {
         if undos.is_empty() {
-            return Ok(());
+            return;
         }
+        self.selection_anchor = None;
+        self.edit_count += 1;
         self.clear_redos(s);
         if let Some(recent) = &mut self.recent {
             recent.commands.extend(undos);
         } else {
-            self.recent = Some(UndoGroup::new(restore_loc, undos));
+            let id = self.next_undo_group_id;
+            self.next_undo_group_id += 1;
+            self.recent = Some(UndoGroup::new(id, restore_loc, undos));
         }
-        Ok(())
     }
 
     /// Groups together all editing commands that have been `.execute()`ed since the last call to
@@ -143,6 +331,7 @@ impl Doc {
     /// the group is automatically ended and then undone.
     pub fn undo(&mut self, s: &mut Storage) -> Result<(), EditError> {
         self.end_undo_group();
+        self.selection_anchor = None;
 
         let undo_group = self.undo_stack.pop().ok_or(EditError::NothingToUndo)?;
         let redo_group = undo_group.execute(s, &mut self.cursor);
@@ -154,6 +343,7 @@ impl Doc {
     /// Returns EditError::NothingToRedo if the redo stack is empty.
     pub fn redo(&mut self, s: &mut Storage) -> Result<(), EditError> {
         let redo_group = self.redo_stack.pop().ok_or(EditError::NothingToRedo)?;
+        self.selection_anchor = None;
         bug_assert!(
             self.recent.is_none(),
             "redo: recent edits should have cleared the redo stack"
@@ -188,9 +378,10 @@ impl Doc {
 }
 
 impl UndoGroup {
-    fn new(restore_loc: Location, commands: Vec<(Location, EdCommand)>) -> UndoGroup {
+    fn new(id: usize, restore_loc: Location, commands: Vec<(Location, EdCommand)>) -> UndoGroup {
         bug_assert!(!commands.is_empty(), "empty undo group");
         UndoGroup {
+            id,
             restore_loc,
             commands,
         }
@@ -212,7 +403,7 @@ impl UndoGroup {
         if let Some(new_cursor) = cursor.exit_text() {
             *cursor = new_cursor;
         }
-        UndoGroup::new(redo_restore_loc.bug(), redos)
+        UndoGroup::new(self.id, redo_restore_loc.bug(), redos)
     }
 
     fn delete_trees(self, s: &mut Storage) {
@@ -288,9 +479,146 @@ fn execute_tree_ed(
             let (old_node, undo_location) = cursor.delete(s, false).ok_or(EditError::NoNodeHere)?;
             Ok(vec![(undo_location, Insert(old_node).into())])
         }
+        Wrap(construct) => {
+            let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+            let wrapper = Node::new_with_auto_fill(s, construct);
+            if !node.swap(s, wrapper) {
+                wrapper.delete_root(s);
+                return Err(EditError::CannotPlaceNode);
+            }
+            if place_in_empty_slot(s, wrapper, node).is_none() {
+                bug_assert!(wrapper.swap(s, node), "wrap: failed to restore node");
+                wrapper.delete_root(s);
+                return Err(EditError::CannotPlaceNode);
+            }
+            *cursor = Location::at(s, wrapper);
+            Ok(vec![(Location::at(s, node), Raise.into())])
+        }
+        Raise => {
+            let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+            let (old_parent, index) = raise(s, node).ok_or(EditError::CannotPlaceNode)?;
+            *cursor = Location::at(s, node);
+            Ok(vec![(*cursor, WrapIn(old_parent, index).into())])
+        }
+        Unwrap => {
+            let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+            let mut opt_child = node.first_child(s);
+            while let Some(child) = opt_child {
+                if !child.construct(s).is_hole(s) {
+                    if let Some((_, index)) = raise(s, child) {
+                        *cursor = Location::at(s, child);
+                        return Ok(vec![(*cursor, WrapIn(node, index).into())]);
+                    }
+                }
+                opt_child = child.next_sibling(s);
+            }
+            Err(EditError::NoReplacementChild)
+        }
+        WrapIn(wrapper, index) => {
+            let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+            if !node.swap(s, wrapper) {
+                return Err(EditError::CannotPlaceNode);
+            }
+            if !place_in_slot(s, wrapper, index, node) {
+                bug_assert!(wrapper.swap(s, node), "wrap_in: failed to restore node");
+                return Err(EditError::CannotPlaceNode);
+            }
+            *cursor = Location::at(s, wrapper);
+            Ok(vec![(Location::at(s, node), Raise.into())])
+        }
+        MoveLeft => move_node(s, cursor, |index, _| index.checked_sub(1)),
+        MoveRight => move_node(s, cursor, |index, last| (index < last).then_some(index + 1)),
+        MoveFirst => move_node(s, cursor, |_, _| Some(0)),
+        MoveLast => move_node(s, cursor, |_, last| Some(last)),
+        MoveTo(new_index) => move_node(s, cursor, |_, last| {
+            (new_index <= last).then_some(new_index)
+        }),
     }
 }
 
+/// Move the node at the cursor to a new position among its siblings in a listy sequence.
+/// `new_index` is given the node's current index and the last index, and returns the index to
+/// move it to.
+fn move_node(
+    s: &mut Storage,
+    cursor: &mut Location,
+    new_index: impl FnOnce(usize, usize) -> Option<usize>,
+) -> Result<Vec<(Location, EdCommand)>, EditError> {
+    let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+    let parent = node.parent(s).ok_or(EditError::NoNodeHere)?;
+    if !matches!(parent.arity(s), Arity::Listy(_)) {
+        return Err(EditError::CannotMove);
+    }
+    let index = node.sibling_index(s);
+    let new_index = new_index(index, node.num_siblings(s) - 1).ok_or(EditError::CannotMove)?;
+    if new_index == index {
+        return Err(EditError::CannotMove);
+    }
+    bug_assert!(node.detach(s), "move_node: failed to detach");
+    bug_assert!(
+        place_in_slot(s, parent, new_index, node),
+        "move_node: failed to reinsert"
+    );
+    *cursor = Location::at(s, node);
+    Ok(vec![(*cursor, TreeEdCommand::MoveTo(index).into())])
+}
+
+/// Put `child` (a root) into the first slot of `parent` that accepts it, if any. In a fixed
+/// sequence, only slots containing holes are considered. Returns the index of the slot.
+fn place_in_empty_slot(s: &mut Storage, parent: Node, child: Node) -> Option<usize> {
+    let num_children = parent.num_children(s)?;
+    match parent.arity(s) {
+        Arity::Texty => None,
+        Arity::Listy(_) => parent.insert_last_child(s, child).then_some(num_children),
+        Arity::Fixed(_) => (0..num_children).find(|i| place_in_slot(s, parent, *i, child)),
+    }
+}
+
+/// Put `child` (a root) into the `index`'th slot of `parent`. In a fixed sequence, that slot must
+/// contain a hole, which gets deleted.
+#[must_use]
+fn place_in_slot(s: &mut Storage, parent: Node, index: usize, child: Node) -> bool {
+    match parent.arity(s) {
+        Arity::Texty => false,
+        Arity::Listy(_) if index == 0 => parent.insert_first_child(s, child),
+        Arity::Listy(_) => match parent.nth_child(s, index - 1) {
+            Some(prev_sibling) => prev_sibling.insert_after(s, child),
+            None => false,
+        },
+        Arity::Fixed(_) => match parent.nth_child(s, index) {
+            Some(hole) if hole.construct(s).is_hole(s) && hole.swap(s, child) => {
+                hole.delete_root(s);
+                true
+            }
+            _ => false,
+        },
+    }
+}
+
+/// Replace the parent of `node` with `node`. Returns the detached parent (which is left with a
+/// hole or gap where `node` was) and the index `node` had in it. Returns `None` and does nothing if
+/// `node` can't take its parent's place.
+fn raise(s: &mut Storage, node: Node) -> Option<(Node, usize)> {
+    let parent = node.parent(s)?;
+    if parent.is_root(s) || !parent.accepts_replacement(s, node) {
+        return None;
+    }
+    let index = node.sibling_index(s);
+    match parent.arity(s) {
+        Arity::Texty => bug!("raise: texty parent"),
+        Arity::Fixed(_) => {
+            let hole = Node::new_hole(s, node.language(s));
+            if !node.swap(s, hole) {
+                hole.delete_root(s);
+                return None;
+            }
+        }
+        Arity::Listy(_) => bug_assert!(node.detach(s), "raise: failed to detach"),
+    }
+    bug_assert!(parent.swap(s, node), "raise: failed to replace parent");
+    Some((parent, index))
+}
+
 fn execute_text_ed(
     s: &mut Storage,
     cmd: TextEdCommand,
@@ -330,49 +658,282 @@ fn execute_clipboard(
     s: &mut Storage,
     cmd: ClipboardCommand,
     cursor: &mut Location,
-    clipboard: &mut Vec<Node>,
+    clipboard: &mut Vec<Vec<Node>>,
+    registers: &mut HashMap<char, Vec<Node>>,
+    selection: Option<(Node, Node)>,
 ) -> Result<Vec<(Location, EdCommand)>, EditError> {
     use ClipboardCommand::*;
 
     match cmd {
         Copy => {
-            let node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
-            clipboard.push(node.deep_copy(s));
+            let copies = copy_nodes(s, *cursor, selection)?;
+            clipboard.push(copies);
             Ok(Vec::new())
         }
         Paste => {
-            let node = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
-            let result = execute_tree_ed(s, TreeEdCommand::Insert(node), cursor);
-            if result.is_err() {
-                clipboard.push(node);
+            let nodes = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
+            match paste_nodes(s, cursor, nodes, selection) {
+                Ok(undos) => Ok(undos),
+                Err((err, nodes)) => {
+                    clipboard.push(nodes);
+                    Err(err)
+                }
+            }
+        }
+        CopyToRegister(register) => {
+            let copies = copy_nodes(s, *cursor, selection)?;
+            if let Some(old_nodes) = registers.insert(register, copies) {
+                for node in old_nodes {
+                    node.delete_root(s);
+                }
+            }
+            Ok(Vec::new())
+        }
+        PasteFromRegister(register) => {
+            let nodes = registers
+                .get(&register)
+                .ok_or(EditError::EmptyRegister(register))?;
+            let copies = nodes.iter().map(|node| node.deep_copy(s)).collect();
+            match paste_nodes(s, cursor, copies, selection) {
+                Ok(undos) => Ok(undos),
+                Err((err, copies)) => {
+                    for node in copies {
+                        node.delete_root(s);
+                    }
+                    Err(err)
+                }
             }
-            result
         }
         PasteSwap => {
-            let clip_node = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
-            let doc_node = cursor.node(s).ok_or(EditError::NoNodeHere)?;
+            let clip_nodes = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
+            // Only a single node can be swapped
+            let (&[clip_node], Some(doc_node), None) =
+                (clip_nodes.as_slice(), cursor.node(s), selection)
+            else {
+                clipboard.push(clip_nodes);
+                return Err(EditError::CannotPlaceNode);
+            };
             if doc_node.swap(s, clip_node) {
                 *cursor = Location::at(s, clip_node);
-                clipboard.push(doc_node.deep_copy(s));
+                clipboard.push(vec![doc_node.deep_copy(s)]);
                 Ok(vec![(*cursor, TreeEdCommand::Replace(doc_node).into())])
             } else {
-                clipboard.push(clip_node);
+                clipboard.push(clip_nodes);
                 Err(EditError::CannotPlaceNode)
             }
         }
         Dup => {
-            let clip_node = clipboard.last().ok_or(EditError::EmptyClipboard)?;
-            clipboard.push(clip_node.deep_copy(s));
+            let clip_nodes = clipboard.last().ok_or(EditError::EmptyClipboard)?.clone();
+            let copies = clip_nodes
+                .into_iter()
+                .map(|node| node.deep_copy(s))
+                .collect();
+            clipboard.push(copies);
             Ok(Vec::new())
         }
         Pop => {
-            let clip_node = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
-            clip_node.delete_root(s);
+            let clip_nodes = clipboard.pop().ok_or(EditError::EmptyClipboard)?;
+            for node in clip_nodes {
+                node.delete_root(s);
+            }
             Ok(Vec::new())
         }
     }
 }
 
+/// Copy the node at the cursor, or the selected nodes if there's a selection.
+fn copy_nodes(
+    s: &mut Storage,
+    cursor: Location,
+    selection: Option<(Node, Node)>,
+) -> Result<Vec<Node>, EditError> {
+    let nodes = match selection {
+        Some((first, last)) => sibling_range(s, first, last),
+        None => vec![cursor.node(s).ok_or(EditError::NoNodeHere)?],
+    };
+    Ok(nodes.into_iter().map(|node| node.deep_copy(s)).collect())
+}
+
+/// Check that all of `nodes` can be inserted at the cursor, one after another.
+fn check_paste(s: &Storage, cursor: Location, nodes: &[Node]) -> Result<(), EditError> {
+    if cursor.mode() != Mode::Tree {
+        return Err(EditError::NotInTreeMode);
+    }
+    let fits = match cursor.parent_node(s).map(|parent| parent.arity(s)) {
+        Some(Arity::Listy(sort)) => nodes.iter().all(|node| sort.accepts(s, node.construct(s))),
+        Some(Arity::Fixed(_)) => match (nodes, cursor.node(s)) {
+            (&[node], Some(old_node)) => old_node.accepts_replacement(s, node),
+            _ => false,
+        },
+        _ => false,
+    };
+    if fits {
+        Ok(())
+    } else {
+        Err(EditError::CannotPlaceNode)
+    }
+}
+
+/// Insert `nodes` at the cursor one after another, replacing the selection if there is one. If
+/// they can't all be inserted, leaves the document unchanged and gives back the nodes.
+fn paste_nodes(
+    s: &mut Storage,
+    cursor: &mut Location,
+    nodes: Vec<Node>,
+    selection: Option<(Node, Node)>,
+) -> Result<Vec<(Location, EdCommand)>, (EditError, Vec<Node>)> {
+    let restore_loc = *cursor;
+    let mut undos = match selection {
+        Some((first, last)) => match delete_range(s, cursor, sibling_range(s, first, last), true) {
+            Ok(undos) => undos,
+            Err(err) => return Err((err, nodes)),
+        },
+        None => Vec::new(),
+    };
+    // Check where the nodes will actually go, after the selection is deleted
+    if let Err(err) = check_paste(s, *cursor, &nodes) {
+        roll_back(s, cursor, restore_loc, undos);
+        return Err((err, nodes));
+    }
+    for node in nodes {
+        let insert = TreeEdCommand::Insert(node);
+        undos.extend(execute_tree_ed(s, insert, cursor).bug_msg("paste: failed to insert"));
+    }
+    Ok(undos)
+}
+
+/// Undo the `undos` of a partly finished edit, and put the cursor back at `restore_loc`. Used to
+/// leave the document unchanged when an edit fails partway through.
+fn roll_back(
+    s: &mut Storage,
+    cursor: &mut Location,
+    restore_loc: Location,
+    undos: Vec<(Location, EdCommand)>,
+) {
+    for (loc, cmd) in undos.into_iter().rev() {
+        jump_to(s, cursor, loc);
+        for (_, redo) in execute_ed(s, cmd, cursor).bug_msg("Failed to roll back") {
+            redo.delete_trees(s);
+        }
+    }
+    jump_to(s, cursor, restore_loc);
+}
+
+/// The siblings from `first` to `last`, inclusive.
+fn sibling_range(s: &Storage, first: Node, last: Node) -> Vec<Node> {
+    let mut nodes = vec![first];
+    let mut node = first;
+    while node != last {
+        node = node
+            .next_sibling(s)
+            .bug_msg("sibling_range: last is not after first");
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Execute a tree editing command on a selected range of siblings `(first, last)`. Commands that
+/// don't make sense on a range act on the node at the cursor alone.
+fn execute_tree_ed_on_range(
+    s: &mut Storage,
+    cmd: TreeEdCommand,
+    cursor: &mut Location,
+    (first, last): (Node, Node),
+) -> Result<Vec<(Location, EdCommand)>, EditError> {
+    use TreeEdCommand::*;
+
+    let nodes = sibling_range(s, first, last);
+    match cmd {
+        Backspace => delete_range(s, cursor, nodes, true),
+        Delete => delete_range(s, cursor, nodes, false),
+        Wrap(construct) => wrap_range(s, cursor, construct, nodes),
+        cmd => execute_tree_ed(s, cmd, cursor),
+    }
+}
+
+/// Delete each of the (sibling) `nodes`, leaving the cursor where `Backspace` (if `move_left`)
+/// or `Delete` of the last one would. On failure, leaves the document unchanged.
+fn delete_range(
+    s: &mut Storage,
+    cursor: &mut Location,
+    nodes: Vec<Node>,
+    move_left: bool,
+) -> Result<Vec<(Location, EdCommand)>, EditError> {
+    let cmd = || {
+        if move_left {
+            TreeEdCommand::Backspace
+        } else {
+            TreeEdCommand::Delete
+        }
+    };
+    let restore_loc = *cursor;
+    let mut undos = Vec::new();
+    for node in nodes {
+        *cursor = Location::at(s, node);
+        match execute_tree_ed(s, cmd(), cursor) {
+            Ok(new_undos) => undos.extend(new_undos),
+            Err(err) => {
+                roll_back(s, cursor, restore_loc, undos);
+                return Err(err);
+            }
+        }
+    }
+    Ok(undos)
+}
+
+/// Wrap the (sibling) `nodes` together in a single new node. If there's more than one of them,
+/// the new node must be listy. On failure, leaves the document unchanged.
+fn wrap_range(
+    s: &mut Storage,
+    cursor: &mut Location,
+    construct: Construct,
+    nodes: Vec<Node>,
+) -> Result<Vec<(Location, EdCommand)>, EditError> {
+    let fits = match construct.arity(s) {
+        Arity::Listy(sort) => nodes.iter().all(|node| sort.accepts(s, node.construct(s))),
+        Arity::Fixed(_) | Arity::Texty => nodes.len() == 1,
+    };
+    if !fits {
+        return Err(EditError::CannotPlaceNode);
+    }
+
+    // Wrap the first node, then move each of the others into the wrapper. Each step is undoable,
+    // and the undo commands own the original nodes, so the wrapper gets copies.
+    let restore_loc = *cursor;
+    let (first, rest) = nodes.split_first().bug();
+    *cursor = Location::at(s, *first);
+    let mut undos = match execute_tree_ed(s, TreeEdCommand::Wrap(construct), cursor) {
+        Ok(undos) => undos,
+        Err(err) => {
+            *cursor = restore_loc;
+            return Err(err);
+        }
+    };
+    let wrapper = cursor.node(s).bug();
+    for node in rest {
+        *cursor = Location::at(s, *node);
+        match execute_tree_ed(s, TreeEdCommand::Delete, cursor) {
+            Ok(delete_undos) => undos.extend(delete_undos),
+            Err(err) => {
+                roll_back(s, cursor, restore_loc, undos);
+                return Err(err);
+            }
+        }
+        let copy = node.deep_copy(s);
+        *cursor = Location::at(s, wrapper.last_child(s).bug());
+        match execute_tree_ed(s, TreeEdCommand::Insert(copy), cursor) {
+            Ok(insert_undos) => undos.extend(insert_undos),
+            Err(err) => {
+                copy.delete_root(s);
+                roll_back(s, cursor, restore_loc, undos);
+                return Err(err);
+            }
+        }
+    }
+    *cursor = Location::at(s, wrapper);
+    Ok(undos)
+}
+
 fn execute_tree_nav(
     s: &Storage,
     cmd: TreeNavCommand,
@@ -393,6 +954,8 @@ fn execute_tree_nav(
         NextLeaf => cursor.next_leaf(s),
         PrevText => cursor.prev_text(s),
         NextText => cursor.next_text(s),
+        PrevHole => cursor.prev_hole(s),
+        NextHole => cursor.next_hole(s),
         Parent => cursor.parent(s),
         FirstChild => cursor.node(s).and_then(|node| {
             Location::at_first_child(s, node).or_else(|| Location::before_children(s, node))
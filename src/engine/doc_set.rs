@@ -2,12 +2,17 @@ use super::doc::Doc;
 use super::Settings;
 use crate::language::Storage;
 use crate::pretty_doc::DocRef;
+use crate::tree::{Bookmark, Location};
+use crate::util::{bug_assert, SynlessBug};
 use partial_pretty_printer as ppp;
 use partial_pretty_printer::pane;
 use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
 
+/// How many positions the jump list remembers.
+const JUMP_LIST_MAX_LEN: usize = 100;
+
 /// Label for documents that might be displayed on the screen.  Not every document will have such a
 /// label, and multiple labels may refer to the same document.
 ///
@@ -40,6 +45,16 @@ use std::path::PathBuf;
 /// |  Aux(key_hints)            |
 /// +----------------------------+
 /// ```
+///
+/// When the screen is split, the other half of the split is labeled `Split`:
+///
+/// ```text
+/// +-------------+--------------+
+/// |             |              |
+/// | Visible     | Split        |
+/// |             |              |
+/// +-------------+--------------+
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DocDisplayLabel {
     /// The "real" document that the user is viewing and editing.
@@ -48,6 +63,9 @@ pub enum DocDisplayLabel {
     Metadata(String),
     /// An auto-generated doc used to implement UI elements like menus.
     Auxilliary(String),
+    /// The document shown in the other half of a split screen, which doesn't have focus. It may be
+    /// the same document as the `Visible` one, but with its own cursor.
+    Split,
 }
 
 /// A unique name for a document.
@@ -75,6 +93,44 @@ pub struct DocSet {
     // TODO: consider more efficient ways to store docs in DocSet
     docs: HashMap<DocName, Doc>,
     visible_doc: Option<DocName>,
+    /// Every doc that has been visible, from most to least recently visible.
+    recent_docs: Vec<DocName>,
+    /// The unfocused half of the screen, if it's split.
+    split_view: Option<SplitView>,
+    jump_list: JumpList,
+    /// While set, `end_undo_group` does nothing, so that a series of commands (like a macro
+    /// replay) is undone all at once.
+    undo_group_boundaries_suppressed: bool,
+}
+
+/// A doc that is being shown but does not have focus, together with its own cursor position.
+#[derive(Debug, Clone)]
+struct SplitView {
+    doc_name: DocName,
+    cursor: Bookmark,
+}
+
+/// Cursor positions from before each large move (like a search or switching docs), so that you
+/// can go back to them. They may be in any doc.
+#[derive(Debug, Default)]
+struct JumpList {
+    jumps: Vec<(DocName, Bookmark)>,
+    /// Where we are in `jumps`. It's `jumps.len()` unless we've gone back.
+    index: usize,
+}
+
+impl JumpList {
+    /// Add a position at the current index, forgetting any positions that were gone back from.
+    fn push(&mut self, doc_name: DocName, mark: Bookmark) {
+        self.jumps.truncate(self.index);
+        if self.jumps.last() != Some(&(doc_name.clone(), mark)) {
+            self.jumps.push((doc_name, mark));
+        }
+        if self.jumps.len() > JUMP_LIST_MAX_LEN {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
 }
 
 impl DocSet {
@@ -82,6 +138,10 @@ impl DocSet {
         DocSet {
             docs: HashMap::new(),
             visible_doc: None,
+            recent_docs: Vec::new(),
+            split_view: None,
+            jump_list: JumpList::default(),
+            undo_group_boundaries_suppressed: false,
         }
     }
 
@@ -94,21 +154,255 @@ impl DocSet {
         true
     }
 
+    /// Deletes the document and all of its nodes. If it was visible, then the previously visible
+    /// document (if any) becomes visible instead. If it was shown in the unfocused half of a split
+    /// screen, then the screen is unsplit. Returns false if there is no such document.
     #[must_use]
-    pub fn delete_doc(&mut self, doc_name: &DocName) -> bool {
-        self.docs.remove(doc_name).is_some()
+    pub fn delete_doc(&mut self, s: &mut Storage, doc_name: &DocName) -> bool {
+        if let Some(doc) = self.docs.remove(doc_name) {
+            doc.delete(s);
+            self.recent_docs.retain(|name| name != doc_name);
+            if self.visible_doc.as_ref() == Some(doc_name) {
+                self.visible_doc = self.recent_docs.first().cloned();
+            }
+            if self.split_view.as_ref().map(|view| &view.doc_name) == Some(doc_name) {
+                self.split_view = None;
+            }
+            let jump_list = &mut self.jump_list;
+            let num_removed_before_index = jump_list.jumps[..jump_list.index]
+                .iter()
+                .filter(|(name, _)| name == doc_name)
+                .count();
+            jump_list.index -= num_removed_before_index;
+            jump_list.jumps.retain(|(name, _)| name != doc_name);
+            true
+        } else {
+            false
+        }
     }
 
+    /// Give the document named `old_name` the name `new_name` instead, keeping it visible if it
+    /// was. Returns false if there is no document named `old_name`, or there already is one named
+    /// `new_name`.
+    #[must_use]
+    pub fn rename_doc(&mut self, old_name: &DocName, new_name: DocName) -> bool {
+        if self.docs.contains_key(&new_name) {
+            return false;
+        }
+        let Some(doc) = self.docs.remove(old_name) else {
+            return false;
+        };
+        if self.visible_doc.as_ref() == Some(old_name) {
+            self.visible_doc = Some(new_name.clone());
+        }
+        for name in &mut self.recent_docs {
+            if name == old_name {
+                *name = new_name.clone();
+            }
+        }
+        if let Some(view) = &mut self.split_view {
+            if &view.doc_name == old_name {
+                view.doc_name = new_name.clone();
+            }
+        }
+        for (name, _) in &mut self.jump_list.jumps {
+            if name == old_name {
+                *name = new_name.clone();
+            }
+        }
+        self.docs.insert(new_name, doc);
+        true
+    }
+
+    /// Make the document visible. Switching away from another doc records a jump.
     #[must_use]
     pub fn set_visible_doc(&mut self, doc_name: &DocName) -> bool {
-        if self.docs.contains_key(doc_name) {
-            self.visible_doc = Some(doc_name.to_owned());
+        if !self.docs.contains_key(doc_name) {
+            return false;
+        }
+        if self.visible_doc.as_ref().map(|name| name != doc_name) == Some(true) {
+            self.record_jump();
+        }
+        self.show_doc(doc_name);
+        true
+    }
+
+    /// Make the document (which must exist) visible, without recording a jump.
+    fn show_doc(&mut self, doc_name: &DocName) {
+        self.visible_doc = Some(doc_name.to_owned());
+        self.recent_docs.retain(|name| name != doc_name);
+        self.recent_docs.insert(0, doc_name.to_owned());
+    }
+
+    /// The docs that have been visible, from most to least recently visible. The first is the
+    /// visible doc.
+    pub fn recent_docs(&self) -> impl Iterator<Item = &DocName> {
+        self.recent_docs.iter()
+    }
+
+    /// Split the screen in two. The unfocused half starts out showing the visible doc, at the same
+    /// cursor position. Returns false if there is no visible doc.
+    #[must_use]
+    pub fn split(&mut self) -> bool {
+        if let Some(doc) = self.visible_doc() {
+            let cursor = doc.cursor().bookmark();
+            let doc_name = self.visible_doc.clone().bug();
+            self.split_view = Some(SplitView { doc_name, cursor });
             true
         } else {
             false
         }
     }
 
+    /// Stop showing the unfocused half of a split screen.
+    pub fn unsplit(&mut self) {
+        self.split_view = None;
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split_view.is_some()
+    }
+
+    /// Move focus to the other half of a split screen: the doc shown there becomes the visible
+    /// doc, and its cursor is restored. Each half keeps its own cursor even if both halves show
+    /// the same doc. Returns false if the screen isn't split or there is no visible doc.
+    #[must_use]
+    pub fn switch_split_focus(&mut self, s: &Storage) -> bool {
+        let (Some(visible_name), Some(other)) = (self.visible_doc.clone(), self.split_view.take())
+        else {
+            return false;
+        };
+        self.end_undo_group();
+        let visible_doc = self.docs.get(&visible_name).bug();
+        self.split_view = Some(SplitView {
+            doc_name: visible_name,
+            cursor: visible_doc.cursor().bookmark(),
+        });
+        bug_assert!(self.docs.contains_key(&other.doc_name));
+        self.show_doc(&other.doc_name);
+        self.end_undo_group();
+        let doc = self.docs.get_mut(&other.doc_name).bug();
+        doc.goto_bookmark(s, other.cursor);
+        true
+    }
+
+    /// Remember the visible doc's cursor position in the jump list. Call this before making a
+    /// large move away from it.
+    pub fn record_jump(&mut self) {
+        if let Some(doc) = self.visible_doc() {
+            let mark = doc.cursor().bookmark();
+            self.record_jump_from(mark);
+        }
+    }
+
+    /// Remember a position in the visible doc in the jump list.
+    pub fn record_jump_from(&mut self, mark: Bookmark) {
+        if let Some(doc_name) = self.visible_doc.clone() {
+            self.jump_list.push(doc_name, mark);
+        }
+    }
+
+    /// Move the visible doc's cursor to `loc`, recording a jump from where it was. Does nothing
+    /// if there is no visible doc.
+    pub fn jump_to(&mut self, loc: Location) {
+        self.record_jump();
+        self.end_undo_group();
+        if let Some(doc) = self.visible_doc_mut() {
+            doc.jump_to(loc);
+        }
+    }
+
+    /// Go back to the previous position in the jump list, skipping positions whose node has been
+    /// deleted. Returns false if there is nowhere to go back to.
+    #[must_use]
+    pub fn jump_back(&mut self, s: &Storage) -> bool {
+        let here = self
+            .visible_doc()
+            .map(|doc| doc.cursor().bookmark())
+            .zip(self.visible_doc.clone());
+        let Some((here_mark, here_name)) = here else {
+            return false;
+        };
+        let Some(index) = (0..self.jump_list.index).rev().find(|i| {
+            self.resolve_jump(s, *i, Some((&here_name, here_mark)))
+                .is_some()
+        }) else {
+            return false;
+        };
+        if self.jump_list.index == self.jump_list.jumps.len() {
+            // Remember where we came from, so that we can go forward to it again.
+            self.jump_list.jumps.push((here_name, here_mark));
+        }
+        self.goto_jump(s, index);
+        true
+    }
+
+    /// Go forward in the jump list, undoing a `jump_back`. Returns false if there is nowhere to
+    /// go forward to.
+    #[must_use]
+    pub fn jump_forward(&mut self, s: &Storage) -> bool {
+        let here = self
+            .visible_doc()
+            .map(|doc| doc.cursor().bookmark())
+            .zip(self.visible_doc.clone());
+        let here = here.as_ref().map(|(mark, name)| (name, *mark));
+        let Some(index) = (self.jump_list.index + 1..self.jump_list.jumps.len())
+            .find(|i| self.resolve_jump(s, *i, here).is_some())
+        else {
+            return false;
+        };
+        self.goto_jump(s, index);
+        true
+    }
+
+    /// The location of the `index`th jump, if its node still exists and it isn't the same as
+    /// `here`.
+    fn resolve_jump(
+        &self,
+        s: &Storage,
+        index: usize,
+        here: Option<(&DocName, Bookmark)>,
+    ) -> Option<Location> {
+        let (doc_name, mark) = &self.jump_list.jumps[index];
+        if here == Some((doc_name, *mark)) {
+            return None;
+        }
+        let doc = self.docs.get(doc_name)?;
+        doc.cursor().validate_bookmark(s, *mark)
+    }
+
+    fn goto_jump(&mut self, s: &Storage, index: usize) {
+        let loc = self.resolve_jump(s, index, None).bug();
+        let doc_name = self.jump_list.jumps[index].0.clone();
+        self.jump_list.index = index;
+        self.end_undo_group();
+        self.show_doc(&doc_name);
+        self.end_undo_group();
+        self.docs.get_mut(&doc_name).bug().jump_to(loc);
+    }
+
+    /// End the visible doc's undo group (see `Doc::end_undo_group`), unless undo group boundaries
+    /// are suppressed.
+    pub fn end_undo_group(&mut self) {
+        if self.undo_group_boundaries_suppressed {
+            return;
+        }
+        if let Some(doc) = self.visible_doc_mut() {
+            doc.end_undo_group();
+        }
+    }
+
+    /// While `suppress` is true, make `end_undo_group` do nothing. Once it's false again, every
+    /// doc's undo group is ended.
+    pub fn suppress_undo_group_boundaries(&mut self, suppress: bool) {
+        self.undo_group_boundaries_suppressed = suppress;
+        if !suppress {
+            for doc in self.docs.values_mut() {
+                doc.end_undo_group();
+            }
+        }
+    }
+
     pub fn visible_doc_name(&self) -> Option<&DocName> {
         self.visible_doc.as_ref()
     }
@@ -121,6 +415,10 @@ impl DocSet {
         self.docs.get_mut(self.visible_doc.as_ref()?)
     }
 
+    pub fn doc_names(&self) -> impl Iterator<Item = &DocName> {
+        self.docs.keys()
+    }
+
     pub fn contains_doc(&self, doc_name: &DocName) -> bool {
         self.docs.contains_key(doc_name)
     }
@@ -147,28 +445,38 @@ impl DocSet {
             set_focus: false,
         };
 
-        let (doc, opts, highlight_cursor) = match label {
+        let cursor_options = |cursor: Location| {
+            let (focus_path, focus_target) = cursor.path_from_root(s);
+            pane::PrintingOptions {
+                focus_path,
+                focus_target,
+                focus_height: settings.focus_height,
+                width_strategy: pane::WidthStrategy::NoMoreThan(settings.max_display_width),
+                set_focus: cursor.node(s).is_none(),
+            }
+        };
+
+        match label {
             DocDisplayLabel::Visible => {
                 let doc = self.get_doc(self.visible_doc_name()?)?;
-                let (focus_path, focus_target) = doc.cursor().path_from_root(s);
-                let options = pane::PrintingOptions {
-                    focus_path,
-                    focus_target,
-                    focus_height: settings.focus_height,
-                    width_strategy: pane::WidthStrategy::NoMoreThan(settings.max_display_width),
-                    set_focus: doc.cursor().node(s).is_none(),
-                };
-                (doc, options, true)
+                let options = cursor_options(doc.cursor());
+                Some((doc.doc_ref_display(s, true), options))
+            }
+            DocDisplayLabel::Split => {
+                let view = self.split_view.as_ref()?;
+                let doc = self.get_doc(&view.doc_name)?;
+                let cursor = doc.resolve_bookmark(s, view.cursor);
+                let options = cursor_options(cursor);
+                Some((doc.doc_ref_display_at(s, cursor), options))
             }
             DocDisplayLabel::Metadata(name) => {
                 let doc = self.get_doc(&DocName::Metadata(name))?;
-                (doc, meta_and_aux_options, false)
+                Some((doc.doc_ref_display(s, false), meta_and_aux_options))
             }
             DocDisplayLabel::Auxilliary(name) => {
                 let doc = self.get_doc(&DocName::Auxilliary(name))?;
-                (doc, meta_and_aux_options, false)
+                Some((doc.doc_ref_display(s, false), meta_and_aux_options))
             }
-        };
-        Some((doc.doc_ref_display(s, highlight_cursor), opts))
+        }
     }
 }
@@ -1,15 +1,18 @@
 #![allow(clippy::module_inception)]
 
-use super::command::Command;
+use super::command::{BookmarkCommand, ClipboardCommand, Command, NavCommand};
 use super::doc::Doc;
 use super::doc_set::{DocDisplayLabel, DocName, DocSet};
+use super::line_map::LineMap;
 use super::Settings;
-use crate::language::{Language, LanguageSpec, NotationSetSpec, Storage};
-use crate::parsing::{Parse, ParseError};
+use crate::language::{Arity, Construct, Language, LanguageSpec, NotationSetSpec, Storage};
+use crate::parsing::{
+    parse_sexpr, parse_with_notation, print_sexpr, Parse, ParseError, TreePattern, TreeTemplate,
+};
 use crate::pretty_doc::DocRef;
 use crate::style::Base16Color;
-use crate::tree::{Mode, Node};
-use crate::util::{bug, error, SynlessBug, SynlessError};
+use crate::tree::{Location, Mode, Node, NodeId, SearchError, SearchQuery};
+use crate::util::{bug, bug_assert, error, SynlessBug, SynlessError};
 use partial_pretty_printer as ppp;
 use partial_pretty_printer::pane;
 use std::collections::HashMap;
@@ -27,6 +30,8 @@ pub enum DocError {
     NoVisibleDoc,
     #[error("Can't create document because it doesn't have a valid root node")]
     InvalidRootNode,
+    #[error("The screen is not split")]
+    NotSplit,
 }
 
 impl From<DocError> for SynlessError {
@@ -40,7 +45,14 @@ pub struct Engine {
     storage: Storage,
     doc_set: DocSet,
     parsers: HashMap<String, Box<dyn Parse + 'static>>,
-    clipboard: Vec<Node>,
+    /// A stack of clips. Each clip is one or more sibling nodes.
+    clipboard: Vec<Vec<Node>>,
+    /// Named registers, each holding a clip.
+    registers: HashMap<char, Vec<Node>>,
+    /// The current search, if any. Its matches are highlighted.
+    search: Option<SearchQuery>,
+    /// The number of edits made to any doc (not counting undo and redo).
+    edit_count: usize,
     settings: Settings,
 }
 
@@ -51,6 +63,9 @@ impl Engine {
             doc_set: DocSet::new(),
             parsers: HashMap::new(),
             clipboard: Vec::new(),
+            registers: HashMap::new(),
+            search: None,
+            edit_count: 0,
             settings,
         }
     }
@@ -167,12 +182,68 @@ impl Engine {
     }
 
     pub fn delete_doc(&mut self, doc_name: &DocName) -> Result<(), SynlessError> {
-        if self.doc_set.delete_doc(doc_name) {
+        if !self.doc_set.delete_doc(&mut self.storage, doc_name) {
             Err(DocError::DocNotFound(doc_name.to_owned()))?;
         }
         Ok(())
     }
 
+    /// The names of all file documents that have unsaved changes.
+    pub fn modified_docs(&self) -> Vec<&DocName> {
+        self.doc_set
+            .doc_names()
+            .filter(|doc_name| matches!(doc_name, DocName::File(_)))
+            .filter(|doc_name| self.doc_set.get_doc(doc_name).bug().is_modified())
+            .collect()
+    }
+
+    pub fn mark_saved(&mut self, doc_name: &DocName) -> Result<(), SynlessError> {
+        let doc = self
+            .doc_set
+            .get_doc_mut(doc_name)
+            .ok_or_else(|| DocError::DocNotFound(doc_name.to_owned()))?;
+        doc.mark_saved();
+        Ok(())
+    }
+
+    pub fn mark_unsaved(&mut self, doc_name: &DocName) -> Result<(), SynlessError> {
+        let doc = self
+            .doc_set
+            .get_doc_mut(doc_name)
+            .ok_or_else(|| DocError::DocNotFound(doc_name.to_owned()))?;
+        doc.mark_unsaved();
+        Ok(())
+    }
+
+    /// The names of all file documents that are open, from most to least recently visible.
+    pub fn file_docs(&self) -> Vec<&DocName> {
+        let mut doc_names = self
+            .doc_set
+            .recent_docs()
+            .filter(|doc_name| matches!(doc_name, DocName::File(_)))
+            .collect::<Vec<_>>();
+        for doc_name in self.doc_set.doc_names() {
+            if matches!(doc_name, DocName::File(_)) && !doc_names.contains(&doc_name) {
+                doc_names.push(doc_name);
+            }
+        }
+        doc_names
+    }
+
+    pub fn rename_doc(
+        &mut self,
+        old_name: &DocName,
+        new_name: DocName,
+    ) -> Result<(), SynlessError> {
+        if !self.doc_set.contains_doc(old_name) {
+            Err(DocError::DocNotFound(old_name.to_owned()))?;
+        }
+        if !self.doc_set.rename_doc(old_name, new_name.clone()) {
+            Err(DocError::DocAlreadyOpen(new_name))?;
+        }
+        Ok(())
+    }
+
     pub fn visible_doc_name(&self) -> Option<&DocName> {
         self.doc_set.visible_doc_name()
     }
@@ -201,15 +272,31 @@ impl Engine {
      ****************************/
 
     pub fn load_doc_from_sexpr(
-        &self,
-        _doc_name: DocName,
-        _source: &str,
+        &mut self,
+        doc_name: DocName,
+        language_name: &str,
+        source: &str,
     ) -> Result<(), SynlessError> {
-        todo!()
+        let language = self.storage.language(language_name)?;
+        let root_node = parse_sexpr(&mut self.storage, language, &doc_name.to_string(), source)?;
+        let doc = Doc::new(&self.storage, root_node).bug_msg("Invalid root");
+        if !self.doc_set.add_doc(doc_name.clone(), doc) {
+            return Err(DocError::DocAlreadyOpen(doc_name).into());
+        }
+        Ok(())
     }
 
-    pub fn print_sexpr(&self, _doc_name: &DocName) -> Result<String, SynlessError> {
-        todo!()
+    pub fn print_sexpr(&self, doc_name: &DocName) -> Result<String, SynlessError> {
+        let doc = self
+            .doc_set
+            .get_doc(doc_name)
+            .ok_or_else(|| DocError::DocNotFound(doc_name.to_owned()))?;
+        let root_node = doc.cursor().root_node(&self.storage);
+        Ok(print_sexpr(
+            &self.storage,
+            root_node,
+            self.settings.max_source_width,
+        ))
     }
 
     pub fn load_doc_from_source(
@@ -218,12 +305,57 @@ impl Engine {
         language_name: &str,
         source: &str,
     ) -> Result<(), SynlessError> {
-        let parser = self
-            .parsers
-            .get_mut(language_name)
-            .ok_or_else(|| error!(Language, "No parser for language {}", language_name))?;
-        let root_node = parser.parse(&mut self.storage, &doc_name.to_string(), source)?;
-        let doc = Doc::new(&self.storage, root_node).bug_msg("Invalid root");
+        let (root_node, _) = self.parse_source(&doc_name, language_name, source, false)?;
+        self.add_parsed_doc(doc_name, root_node, source)
+    }
+
+    /// Like `load_doc_from_source`, but if the source is malformed, keep as much of it as
+    /// possible instead of failing. Returns the parse errors that were recovered from.
+    pub fn load_doc_from_source_with_recovery(
+        &mut self,
+        doc_name: DocName,
+        language_name: &str,
+        source: &str,
+    ) -> Result<Vec<ParseError>, SynlessError> {
+        let (root_node, errors) = self.parse_source(&doc_name, language_name, source, true)?;
+        self.add_parsed_doc(doc_name, root_node, source)?;
+        Ok(errors)
+    }
+
+    fn parse_source(
+        &mut self,
+        doc_name: &DocName,
+        language_name: &str,
+        source: &str,
+        recover: bool,
+    ) -> Result<(Node, Vec<ParseError>), SynlessError> {
+        let file_name = doc_name.to_string();
+        if let Some(parser) = self.parsers.get_mut(language_name) {
+            if recover {
+                parser.parse_with_recovery(&mut self.storage, &file_name, source)
+            } else {
+                let root_node = parser.parse(&mut self.storage, &file_name, source)?;
+                Ok((root_node, Vec::new()))
+            }
+        } else {
+            // Fall back to parsing using the language's source notation
+            let language = self.storage.language(language_name)?;
+            if language.source_notation(&self.storage).is_none() {
+                return Err(error!(Language, "No parser for language {}", language_name));
+            }
+            let root_node = parse_with_notation(&mut self.storage, language, &file_name, source)?;
+            Ok((root_node, Vec::new()))
+        }
+    }
+
+    fn add_parsed_doc(
+        &mut self,
+        doc_name: DocName,
+        root_node: Node,
+        source: &str,
+    ) -> Result<(), SynlessError> {
+        let mut doc = Doc::new(&self.storage, root_node).bug_msg("Invalid root");
+        doc.set_ends_with_newline(source.ends_with('\n'));
         if !self.doc_set.add_doc(doc_name.clone(), doc) {
             return Err(DocError::DocAlreadyOpen(doc_name).into());
         }
@@ -238,19 +370,131 @@ impl Engine {
         }
     }
 
+    /// Split the screen in two, showing the visible doc in both halves.
+    pub fn split(&mut self) -> Result<(), SynlessError> {
+        if self.doc_set.split() {
+            Ok(())
+        } else {
+            Err(DocError::NoVisibleDoc.into())
+        }
+    }
+
+    pub fn unsplit(&mut self) {
+        self.doc_set.unsplit();
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.doc_set.is_split()
+    }
+
+    /// Move focus to the other half of a split screen.
+    pub fn switch_split_focus(&mut self) -> Result<(), SynlessError> {
+        if self.doc_set.switch_split_focus(&self.storage) {
+            Ok(())
+        } else {
+            Err(DocError::NotSplit.into())
+        }
+    }
+
+    /// Print the doc using its source notation.
     pub fn print_source(&self, doc_name: &DocName) -> Result<String, SynlessError> {
         let doc = self
             .doc_set
             .get_doc(doc_name)
             .ok_or_else(|| DocError::DocNotFound(doc_name.to_owned()))?;
-        let doc_ref = doc.doc_ref_source(&self.storage, false);
-        let source = ppp::pretty_print_to_string(doc_ref, self.settings.max_source_width)?;
+        let (source, _num_lines) = self.print_doc_source(doc)?;
         Ok(source)
     }
 
-    pub fn get_content(&self, label: DocDisplayLabel) -> Option<(DocRef, pane::PrintingOptions)> {
+    /// Like `print_source`, but also records which line each node was printed on, for
+    /// `line_number_at_cursor` and `goto_line`. Used when saving, so that line numbers match the
+    /// file on disk.
+    pub fn print_source_and_record_lines(
+        &mut self,
+        doc_name: &DocName,
+    ) -> Result<String, SynlessError> {
+        let doc = self
+            .doc_set
+            .get_doc(doc_name)
+            .ok_or_else(|| DocError::DocNotFound(doc_name.to_owned()))?;
+        let (source, num_lines) = self.print_doc_source(doc)?;
+        let line_map = LineMap::compute(
+            &self.storage,
+            doc.cursor().root_node(&self.storage),
+            doc.doc_ref_source(&self.storage, false),
+            self.settings.max_source_width,
+            num_lines,
+        )?;
         self.doc_set
-            .get_content(&self.storage, label, &self.settings)
+            .get_doc_mut(doc_name)
+            .bug()
+            .set_line_map(line_map);
+        Ok(source)
+    }
+
+    /// Print the doc using its source notation, returning the source and its number of lines.
+    fn print_doc_source(&self, doc: &Doc) -> Result<(String, usize), SynlessError> {
+        let doc_ref = doc.doc_ref_source(&self.storage, false);
+        let printed = ppp::pretty_print_to_string(doc_ref, self.settings.max_source_width)?;
+        let trim_blank_lines = doc
+            .cursor()
+            .root_node(&self.storage)
+            .language(&self.storage)
+            .trims_blank_lines(&self.storage);
+        let lines = printed
+            .split('\n')
+            .map(|line| {
+                if trim_blank_lines && line.trim().is_empty() {
+                    ""
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut source = lines.join("\n");
+        if doc.ends_with_newline() {
+            source.push('\n');
+        }
+        Ok((source, lines.len()))
+    }
+
+    /// Print the node at the cursor (or the selected nodes, one per line) using the source
+    /// notation.
+    pub fn print_source_at_cursor(&self) -> Result<String, SynlessError> {
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        let nodes = match doc.selection(&self.storage) {
+            Some((first, last)) => {
+                let mut nodes = vec![first];
+                while *nodes.last().bug() != last {
+                    nodes.push(nodes.last().bug().next_sibling(&self.storage).bug());
+                }
+                nodes
+            }
+            None => vec![doc
+                .cursor()
+                .node(&self.storage)
+                .ok_or_else(|| error!(Edit, "No node to print here"))?],
+        };
+        let mut sources = Vec::new();
+        for node in nodes {
+            let doc_ref = DocRef::new_source(&self.storage, None, node);
+            sources.push(ppp::pretty_print_to_string(
+                doc_ref,
+                self.settings.max_source_width,
+            )?);
+        }
+        Ok(sources.join("\n"))
+    }
+
+    pub fn get_content(&self, label: DocDisplayLabel) -> Option<(DocRef, pane::PrintingOptions)> {
+        let is_main_doc = matches!(label, DocDisplayLabel::Visible | DocDisplayLabel::Split);
+        let (doc_ref, options) = self
+            .doc_set
+            .get_content(&self.storage, label, &self.settings)?;
+        match &self.search {
+            Some(search) if is_main_doc => Some((doc_ref.with_search(search), options)),
+            _ => Some((doc_ref, options)),
+        }
     }
 
     pub fn make_string_doc(&mut self, string: String, bg_color: Option<Base16Color>) -> Node {
@@ -279,11 +523,127 @@ impl Engine {
      ***********/
 
     pub fn execute(&mut self, cmd: impl Into<Command>) -> Result<(), SynlessError> {
+        let cmd = cmd.into();
         let doc = self
             .doc_set
             .visible_doc_mut()
             .ok_or(DocError::NoVisibleDoc)?;
-        doc.execute(&mut self.storage, cmd.into(), &mut self.clipboard)?;
+        // Going to a bookmark is a large move, so it goes in the jump list.
+        let jump_from = matches!(
+            cmd,
+            Command::Nav(NavCommand::Bookmark(BookmarkCommand::Goto(_)))
+        )
+        .then(|| doc.cursor().bookmark());
+        let old_edit_count = doc.edit_count();
+        doc.execute(
+            &mut self.storage,
+            cmd,
+            &mut self.clipboard,
+            &mut self.registers,
+        )?;
+        self.edit_count += doc.edit_count() - old_edit_count;
+        if let Some(mark) = jump_from {
+            self.doc_set.record_jump_from(mark);
+        }
+        Ok(())
+    }
+
+    /// Go back to where the cursor was before the last large move (like a search, or switching
+    /// docs).
+    pub fn jump_back(&mut self) -> Result<(), SynlessError> {
+        if self.doc_set.jump_back(&self.storage) {
+            Ok(())
+        } else {
+            Err(error!(Edit, "No earlier position in the jump list"))
+        }
+    }
+
+    /// Undo a `jump_back`.
+    pub fn jump_forward(&mut self) -> Result<(), SynlessError> {
+        if self.doc_set.jump_forward(&self.storage) {
+            Ok(())
+        } else {
+            Err(error!(Edit, "No later position in the jump list"))
+        }
+    }
+
+    /// The number of edits that have been made to any doc (not counting undo and redo). Can be
+    /// compared before and after some action to see whether it edited anything.
+    pub fn edit_count(&self) -> usize {
+        self.edit_count
+    }
+
+    /// The names of all non-empty registers in order, each with a one-line preview of its contents
+    /// (printed using their display notation).
+    pub fn list_registers(&self) -> Result<Vec<(char, String)>, SynlessError> {
+        let mut names = self.registers.keys().copied().collect::<Vec<_>>();
+        names.sort();
+        let mut registers = Vec::new();
+        for name in names {
+            let mut previews = Vec::new();
+            for node in &self.registers[&name] {
+                let doc_ref = DocRef::new_display(&self.storage, None, *node);
+                let printed =
+                    ppp::pretty_print_to_string(doc_ref, self.settings.max_display_width)?;
+                previews.push(printed.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+            registers.push((name, previews.join(" ")));
+        }
+        Ok(registers)
+    }
+
+    /// Parse `source` using the visible doc's language, and paste the resulting nodes at the
+    /// cursor (in the same manner as `ClipboardCommand::Paste`). The parsed root node itself is
+    /// dropped; its children are what get pasted.
+    pub fn paste_source(&mut self, source: &str) -> Result<(), SynlessError> {
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        let language = doc
+            .cursor()
+            .root_node(&self.storage)
+            .language(&self.storage);
+        let language_name = language.name(&self.storage).to_owned();
+        let doc_name = DocName::Auxilliary("<paste>".to_owned());
+        let (root, _) = self.parse_source(&doc_name, &language_name, source, false)?;
+
+        let s = &mut self.storage;
+        let mut nodes = Vec::new();
+        if let Arity::Listy(_) = root.arity(s) {
+            // Detaching a child shifts the rest left, so keep taking the first one
+            while let Some(child) = root.first_child(s) {
+                bug_assert!(child.detach(s));
+                nodes.push(child);
+            }
+        } else {
+            while let Some(child) = root.nth_child(s, nodes.len()) {
+                let hole = Node::new_hole(s, language);
+                bug_assert!(child.swap(s, hole));
+                nodes.push(child);
+            }
+        }
+        root.delete_root(s);
+
+        self.clipboard.push(nodes);
+        if let Err(err) = self.execute(ClipboardCommand::Paste) {
+            for node in self.clipboard.pop().bug() {
+                node.delete_root(&mut self.storage);
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Start selecting a range of siblings at the cursor, or stop selecting if there already is
+    /// a selection.
+    pub fn toggle_selection(&mut self) -> Result<(), SynlessError> {
+        let doc = self
+            .doc_set
+            .visible_doc_mut()
+            .ok_or(DocError::NoVisibleDoc)?;
+        if doc.selection(&self.storage).is_some() {
+            doc.clear_selection();
+        } else {
+            doc.start_selection(&self.storage)?;
+        }
         Ok(())
     }
 
@@ -305,15 +665,223 @@ impl Engine {
         Ok(())
     }
 
+    /// End the visible doc's undo group, unless undo group boundaries are suppressed.
     pub fn end_undo_group(&mut self) -> Result<(), SynlessError> {
+        self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        self.doc_set.end_undo_group();
+        Ok(())
+    }
+
+    /// While `suppress` is true, don't end any undo groups (except by undoing or saving), so that
+    /// everything done in the meantime is undone at once.
+    pub fn suppress_undo_group_boundaries(&mut self, suppress: bool) {
+        self.doc_set.suppress_undo_group_boundaries(suppress);
+    }
+
+    /// The line of source that the node at the cursor was on, when the visible doc was last saved.
+    pub fn line_number_at_cursor(&self) -> Option<usize> {
+        let doc = self.doc_set.visible_doc()?;
+        let cursor = doc.cursor();
+        let node = cursor
+            .node(&self.storage)
+            .or_else(|| cursor.text_pos().map(|(node, _)| node))
+            .or_else(|| cursor.parent_node(&self.storage))?;
+        doc.line_map().line(&self.storage, node)
+    }
+
+    /// Move the cursor to the node that was on `line` (starting at 1) when the visible doc was last
+    /// saved.
+    pub fn goto_line(&mut self, line: usize) -> Result<(), SynlessError> {
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        if doc.line_map().is_empty() {
+            return Err(error!(Edit, "Line numbers are only known after saving"));
+        }
+        let root = doc.cursor().root_node(&self.storage);
+        let node = doc
+            .line_map()
+            .node_at_line(&self.storage, root, line)
+            .ok_or_else(|| error!(Edit, "There is no line {}", line))?;
+        self.doc_set.jump_to(Location::at(&self.storage, node));
+        Ok(())
+    }
+
+    /// The number of holes in the visible doc.
+    pub fn num_holes(&self) -> Option<usize> {
+        let doc = self.doc_set.visible_doc()?;
+        let root = doc.cursor().root_node(&self.storage);
+        Some(root.num_holes(&self.storage))
+    }
+
+    /// Find the node with the given id in the visible doc, if it's there.
+    pub fn find_visible_node(&self, id: NodeId) -> Option<Node> {
+        let doc = self.doc_set.visible_doc()?;
+        doc.cursor()
+            .root_node(&self.storage)
+            .find_descendant(&self.storage, id)
+    }
+
+    /// Move the cursor of the visible doc to `node`. If `char_index` is given and the node is
+    /// texty, the cursor goes inside its text, before that character. The cursor can't be on the
+    /// root, so jumping to the root goes to the start of its children instead.
+    pub fn jump_to_node(
+        &mut self,
+        node: Node,
+        char_index: Option<usize>,
+    ) -> Result<(), SynlessError> {
+        self.doc_set.end_undo_group();
+        let s = &self.storage;
         let doc = self
             .doc_set
             .visible_doc_mut()
             .ok_or(DocError::NoVisibleDoc)?;
-        doc.end_undo_group();
+        if node.root(s) != doc.cursor().root_node(s) {
+            return Err(error!(Edit, "That node is not in the visible document"));
+        }
+        let loc = if node.is_root(s) {
+            Location::before_children(s, node)
+                .ok_or_else(|| error!(Edit, "The document has nowhere to put the cursor"))?
+        } else if let Some(loc) = char_index.and_then(|i| Location::in_text(s, node, i)) {
+            loc
+        } else {
+            Location::at(s, node)
+        };
+        doc.jump_to(loc);
+        Ok(())
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /**********
+     * Search *
+     **********/
+
+    /// Set the search query (see [`SearchQuery::parse`] for its syntax). An empty query clears the
+    /// search.
+    pub fn set_search(&mut self, query: &str) -> Result<(), SynlessError> {
+        self.search = if query.is_empty() {
+            None
+        } else {
+            Some(SearchQuery::parse(query)?)
+        };
         Ok(())
     }
 
+    /// Search for nodes of the given construct.
+    pub fn set_search_construct(&mut self, construct: Construct) -> Result<(), SynlessError> {
+        let name = construct.name(&self.storage);
+        self.set_search(&format!(":{}", name))
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_ref().map(|search| search.as_str())
+    }
+
+    /// The number of matches for the current search in the visible doc.
+    pub fn num_search_matches(&self) -> usize {
+        match (&self.search, self.doc_set.visible_doc()) {
+            (Some(search), Some(doc)) => search
+                .find_all(&self.storage, doc.cursor().root_node(&self.storage))
+                .len(),
+            _ => 0,
+        }
+    }
+
+    /// Move the cursor to the next match of the current search, wrapping around to the start of
+    /// the doc. Or to the previous match, if `backwards`.
+    pub fn search_next(&mut self, backwards: bool) -> Result<(), SynlessError> {
+        let search = self.search.as_ref().ok_or(SearchError::NoQuery)?;
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        let loc = search
+            .find_from(&self.storage, doc.cursor(), backwards)
+            .ok_or_else(|| SearchError::NoMatches(search.as_str().to_owned()))?;
+        self.doc_set.jump_to(loc);
+        Ok(())
+    }
+
+    /// For each node in the visible doc that matches `pattern`, the source of that node and the
+    /// source of what it would be replaced with by `template`. See the `parsing::pattern` module
+    /// for the syntax of patterns and templates.
+    pub fn preview_replacements(
+        &mut self,
+        pattern: &str,
+        template: &str,
+    ) -> Result<Vec<(String, String)>, SynlessError> {
+        let (root, pattern, template) = self.parse_pattern_and_template(pattern, template)?;
+        let width = self.settings.max_source_width;
+        let mut previews = Vec::new();
+        for node in pattern.find_all(&self.storage, root) {
+            let new_node = template.instantiate(&mut self.storage, &pattern, node)?;
+            let old_source = DocRef::new_source(&self.storage, None, node);
+            let new_source = DocRef::new_source(&self.storage, None, new_node);
+            let printed = ppp::pretty_print_to_string(old_source, width).and_then(|old| {
+                ppp::pretty_print_to_string(new_source, width).map(|new| (old, new))
+            });
+            new_node.delete_root(&mut self.storage);
+            previews.push(printed?);
+        }
+        Ok(previews)
+    }
+
+    /// Move the cursor to the `index`th node in the visible doc that matches `pattern`.
+    pub fn goto_pattern_match(&mut self, pattern: &str, index: usize) -> Result<(), SynlessError> {
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        let root = doc.cursor().root_node(&self.storage);
+        let pattern = TreePattern::parse(&self.storage, root.language(&self.storage), pattern)?;
+        let node = *pattern
+            .find_all(&self.storage, root)
+            .get(index)
+            .ok_or_else(|| error!(Edit, "There is no match number {}", index))?;
+        self.doc_set.jump_to(Location::at(&self.storage, node));
+        Ok(())
+    }
+
+    /// Replace every node in the visible doc that matches `pattern` using `template`, as a single
+    /// undo group. Returns the number of nodes replaced.
+    pub fn replace_all(&mut self, pattern: &str, template: &str) -> Result<usize, SynlessError> {
+        let (root, pattern, template) = self.parse_pattern_and_template(pattern, template)?;
+        let mut replacements = Vec::new();
+        for node in pattern.find_all(&self.storage, root) {
+            match template.instantiate(&mut self.storage, &pattern, node) {
+                Ok(new_node) => replacements.push((node, new_node)),
+                Err(err) => {
+                    for (_, new_node) in replacements {
+                        new_node.delete_root(&mut self.storage);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        let count = replacements.len();
+        self.doc_set.end_undo_group();
+        let doc = self.doc_set.visible_doc_mut().bug();
+        let old_edit_count = doc.edit_count();
+        doc.replace_nodes(&mut self.storage, replacements)?;
+        self.edit_count += doc.edit_count() - old_edit_count;
+        self.doc_set.end_undo_group();
+        Ok(count)
+    }
+
+    /// Parse a pattern and template in the language of the visible doc, and return them along
+    /// with that doc's root node.
+    fn parse_pattern_and_template(
+        &self,
+        pattern: &str,
+        template: &str,
+    ) -> Result<(Node, TreePattern, TreeTemplate), SynlessError> {
+        let doc = self.doc_set.visible_doc().ok_or(DocError::NoVisibleDoc)?;
+        let root = doc.cursor().root_node(&self.storage);
+        let language = root.language(&self.storage);
+        let pattern = TreePattern::parse(&self.storage, language, pattern)?;
+        let template = TreeTemplate::parse(&self.storage, language, template, &pattern)?;
+        Ok((root, pattern, template))
+    }
+
     /**********************
      * Raw Storage Access *
      **********************/
@@ -0,0 +1,154 @@
+use crate::language::Storage;
+use crate::pretty_doc::DocRef;
+use crate::style::Style;
+use crate::tree::{Node, NodeId};
+use crate::util::SynlessError;
+use partial_pretty_printer as ppp;
+use partial_pretty_printer::pane::{self, PrettyWindow};
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// For each node in a document, the first line of source that it was printed on, as of the last
+/// time its source was printed. Line numbers start at 1, to match what compilers and linters say.
+#[derive(Debug, Clone, Default)]
+pub struct LineMap {
+    lines: HashMap<NodeId, usize>,
+}
+
+impl LineMap {
+    /// Record the lines of every node under `root`, when `doc_ref` (which must be for `root`) is
+    /// printed as `num_lines` lines at the given width.
+    pub fn compute(
+        s: &Storage,
+        root: Node,
+        doc_ref: DocRef,
+        width: ppp::Width,
+        num_lines: usize,
+    ) -> Result<LineMap, SynlessError> {
+        let mut window = LineRecorder {
+            size: ppp::Size {
+                width,
+                height: num_lines as ppp::Height,
+            },
+            lines: HashMap::new(),
+        };
+        let note = pane::PaneNotation::Doc { label: () };
+        let get_content = |()| {
+            let options = pane::PrintingOptions {
+                focus_path: vec![],
+                focus_target: ppp::FocusTarget::Start,
+                focus_height: 0.0,
+                width_strategy: pane::WidthStrategy::Full,
+                set_focus: false,
+            };
+            Some((doc_ref, options))
+        };
+        pane::display_pane(&mut window, &note, &Style::default(), &get_content)?;
+        let mut line_map = LineMap {
+            lines: window.lines,
+        };
+        // So far each node only has the line of the first character it printed itself, not
+        // counting what its descendants printed.
+        line_map.include_descendants(s, root);
+        Ok(line_map)
+    }
+
+    /// Set each node's line to the earliest line of any node in its subtree. Returns the line of
+    /// `node`.
+    fn include_descendants(&mut self, s: &Storage, node: Node) -> Option<usize> {
+        let mut line = self.lines.get(&node.id(s)).copied();
+        let mut child = node.first_child(s);
+        while let Some(child_node) = child {
+            if let Some(child_line) = self.include_descendants(s, child_node) {
+                line = Some(line.map_or(child_line, |line| line.min(child_line)));
+            }
+            child = child_node.next_sibling(s);
+        }
+        if let Some(line) = line {
+            self.lines.insert(node.id(s), line);
+        }
+        line
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The line that `node` was first printed on. A node that printed nothing (like one with empty
+    /// text), or that didn't exist yet when the line map was made, gets its parent's line.
+    pub fn line(&self, s: &Storage, node: Node) -> Option<usize> {
+        let mut node = node;
+        loop {
+            if let Some(line) = self.lines.get(&node.id(s)) {
+                return Some(*line);
+            }
+            node = node.parent(s)?;
+        }
+    }
+
+    /// The outermost node under `root` that was first printed on `line`. If none was, then the
+    /// outermost node first printed on the closest line before it.
+    pub fn node_at_line(&self, s: &Storage, root: Node, line: usize) -> Option<Node> {
+        let mut best: Option<(usize, Node)> = None;
+        let mut stack = root.first_child(s).into_iter().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if let Some(sibling) = node.next_sibling(s) {
+                stack.push(sibling);
+            }
+            let Some(node_line) = self.lines.get(&node.id(s)).copied() else {
+                continue;
+            };
+            if node_line > line {
+                continue;
+            }
+            if best
+                .map(|(best_line, _)| node_line > best_line)
+                .unwrap_or(true)
+            {
+                best = Some((node_line, node));
+            }
+            if let Some(child) = node.first_child(s) {
+                stack.push(child);
+            }
+        }
+        best.map(|(_, node)| node)
+    }
+}
+
+/// A window that doesn't show anything, but records the first row that each node printed on.
+struct LineRecorder {
+    size: ppp::Size,
+    lines: HashMap<NodeId, usize>,
+}
+
+impl PrettyWindow for LineRecorder {
+    type Error = Infallible;
+    type Style = Style;
+
+    fn size(&self) -> Result<ppp::Size, Infallible> {
+        Ok(self.size)
+    }
+
+    fn display_char(
+        &mut self,
+        ch: char,
+        pos: ppp::Pos,
+        style: &Style,
+        _full_width: bool,
+    ) -> Result<(), Infallible> {
+        if let Some(id) = style.node_id {
+            if !ch.is_whitespace() {
+                let line = pos.row as usize + 1;
+                self.lines
+                    .entry(id)
+                    .and_modify(|old| *old = (*old).min(line))
+                    .or_insert(line);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_focus(&mut self, _pos: ppp::Pos) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
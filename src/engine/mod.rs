@@ -2,9 +2,12 @@ mod command;
 mod doc;
 mod doc_set;
 mod engine;
+mod line_map;
 
 use partial_pretty_printer as ppp;
 use std::default::Default;
+use std::path::PathBuf;
+use std::time::Duration;
 
 pub use command::{
     BookmarkCommand, ClipboardCommand, TextEdCommand, TextNavCommand, TreeEdCommand, TreeNavCommand,
@@ -17,6 +20,10 @@ pub struct Settings {
     max_source_width: ppp::Width,
     max_display_width: ppp::Width,
     focus_height: f32,
+    /// Where to save the contents of modified documents, so they can be recovered after a crash.
+    pub recovery_dir: PathBuf,
+    /// How often to save the contents of modified documents to the `recovery_dir`.
+    pub autosave_interval: Duration,
 }
 
 impl Default for Settings {
@@ -25,6 +32,8 @@ impl Default for Settings {
             max_source_width: 100,
             max_display_width: 120,
             focus_height: 0.25,
+            recovery_dir: std::env::temp_dir().join("synless_recovery"),
+            autosave_interval: Duration::from_secs(30),
         }
     }
 }
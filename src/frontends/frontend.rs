@@ -21,6 +21,9 @@ pub trait Frontend: Sized + ppp::pane::PrettyWindow {
 
     /// Show the modified frame to the user. This must be called after pretty-printing.
     fn end_frame(&mut self) -> Result<(), Self::Error>;
+
+    /// Put the given text on the operating system's clipboard, if the frontend is able to.
+    fn set_clipboard(&mut self, text: &str) -> Result<(), Self::Error>;
 }
 
 /// An input event.
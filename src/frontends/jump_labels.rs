@@ -0,0 +1,215 @@
+use super::screen_map::ScreenMap;
+use crate::language::Storage;
+use crate::style::{Style, JUMP_LABEL_STYLE};
+use crate::tree::{Node, NodeId};
+use crate::util::{error, SynlessError};
+use partial_pretty_printer::pane::PrettyWindow;
+use partial_pretty_printer::{Col, Pos, Row};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The characters that labels are made of, easiest to type first.
+const LABEL_CHARS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Which nodes to put jump labels on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpTargetKind {
+    All,
+    /// Nodes without children.
+    Leaves,
+    Texty,
+    Holes,
+}
+
+impl JumpTargetKind {
+    fn includes(self, s: &Storage, node: Node) -> bool {
+        match self {
+            JumpTargetKind::All => true,
+            JumpTargetKind::Leaves => node.first_child(s).is_none(),
+            JumpTargetKind::Texty => node.is_texty(s),
+            JumpTargetKind::Holes => node.construct(s).is_hole(s),
+        }
+    }
+}
+
+impl FromStr for JumpTargetKind {
+    type Err = SynlessError;
+
+    fn from_str(s: &str) -> Result<JumpTargetKind, SynlessError> {
+        match s {
+            "all" => Ok(JumpTargetKind::All),
+            "leaves" => Ok(JumpTargetKind::Leaves),
+            "texty" => Ok(JumpTargetKind::Texty),
+            "holes" => Ok(JumpTargetKind::Holes),
+            _ => Err(error!(
+                Keymap,
+                "Unknown kind of jump target '{}' (expected all, leaves, texty, or holes)", s
+            )),
+        }
+    }
+}
+
+/// Short labels overlaid on the nodes that are on screen, so that you can jump to a node by typing
+/// its label. All labels have the same length, so none is a prefix of another.
+#[derive(Debug)]
+pub struct JumpLabels {
+    targets: Vec<JumpTarget>,
+}
+
+#[derive(Debug)]
+struct JumpTarget {
+    label: String,
+    node: Node,
+    /// The node that printed the first character of `node` (maybe `node` itself, or one of its
+    /// descendants). The label is drawn over that character.
+    anchor: NodeId,
+}
+
+impl JumpLabels {
+    /// Label the nodes of the given kind under `root` (but not `root` itself) that are shown in
+    /// the `screen_map`, in the order they appear on the screen. If several nodes start at the
+    /// same place, only the outermost gets a label.
+    pub fn new(
+        s: &Storage,
+        root: Node,
+        kind: JumpTargetKind,
+        screen_map: &ScreenMap,
+    ) -> JumpLabels {
+        let positions = screen_map.first_positions();
+        let mut found = Vec::new();
+        let mut child = root.first_child(s);
+        while let Some(node) = child {
+            find_targets(s, node, kind, &positions, 0, &mut found);
+            child = node.next_sibling(s);
+        }
+        found.sort_by_key(|(pos, depth, _, _)| (pos.row, pos.col, *depth));
+        found.dedup_by_key(|(pos, _, _, _)| (pos.row, pos.col));
+        found.truncate(LABEL_CHARS.len().pow(2));
+        let labels = make_labels(found.len());
+        let targets = found
+            .into_iter()
+            .zip(labels)
+            .map(|((_, _, node, anchor), label)| JumpTarget {
+                label,
+                node,
+                anchor,
+            })
+            .collect();
+        JumpLabels { targets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// The node with this label, if any.
+    pub fn lookup(&self, label: &str) -> Option<Node> {
+        self.targets
+            .iter()
+            .find(|target| target.label == label)
+            .map(|target| target.node)
+    }
+
+    /// Whether any label starts with `prefix`.
+    pub fn has_prefix(&self, prefix: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.label.starts_with(prefix))
+    }
+
+    /// Draw the labels that start with `typed` over the nodes they label, leaving off the part
+    /// that's already been typed. Labels of nodes that are no longer on screen are skipped.
+    pub fn display<W: PrettyWindow<Style = Style>>(
+        &self,
+        window: &mut W,
+        screen_map: &ScreenMap,
+        typed: &str,
+    ) -> Result<(), W::Error> {
+        let positions = screen_map.first_positions();
+        let width = window.size()?.width;
+        for target in &self.targets {
+            let (Some(rest), Some(pos)) = (
+                target.label.strip_prefix(typed),
+                positions.get(&target.anchor),
+            ) else {
+                continue;
+            };
+            for (i, ch) in rest.chars().enumerate() {
+                let col = pos.col + i as Col;
+                if col >= width {
+                    break;
+                }
+                window.display_char(ch, Pos { row: pos.row, col }, &JUMP_LABEL_STYLE, false)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Make `count` distinct labels, all of the same length: one character if there are few enough,
+/// otherwise two.
+fn make_labels(count: usize) -> Vec<String> {
+    let chars = LABEL_CHARS.chars().collect::<Vec<_>>();
+    if count <= chars.len() {
+        chars[..count].iter().map(|ch| ch.to_string()).collect()
+    } else {
+        chars
+            .iter()
+            .flat_map(|first| {
+                chars
+                    .iter()
+                    .map(move |second| format!("{}{}", first, second))
+            })
+            .take(count)
+            .collect()
+    }
+}
+
+/// Find the targets under `node` (inclusive), each with its position on screen, depth, and anchor.
+/// Returns the position and anchor of the first character printed by `node` or its descendants.
+fn find_targets(
+    s: &Storage,
+    node: Node,
+    kind: JumpTargetKind,
+    positions: &HashMap<NodeId, Pos>,
+    depth: usize,
+    found: &mut Vec<(Pos, usize, Node, NodeId)>,
+) -> Option<(Pos, NodeId)> {
+    let order = |pos: Pos| -> (Row, Col) { (pos.row, pos.col) };
+
+    let mut first = positions.get(&node.id(s)).map(|pos| (*pos, node.id(s)));
+    let mut child = node.first_child(s);
+    while let Some(child_node) = child {
+        if let Some((pos, anchor)) = find_targets(s, child_node, kind, positions, depth + 1, found)
+        {
+            if first.map_or(true, |(first_pos, _)| order(pos) < order(first_pos)) {
+                first = Some((pos, anchor));
+            }
+        }
+        child = child_node.next_sibling(s);
+    }
+    if let Some((pos, anchor)) = first {
+        if kind.includes(s, node) {
+            found.push((pos, depth, node, anchor));
+        }
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_labels() {
+        assert_eq!(make_labels(0), Vec::<String>::new());
+        assert_eq!(make_labels(3), ["a", "s", "d"]);
+        assert_eq!(make_labels(26).last().unwrap(), "m");
+
+        let labels = make_labels(30);
+        assert_eq!(labels.len(), 30);
+        assert_eq!(&labels[..3], ["aa", "as", "ad"]);
+        assert_eq!(labels[26], "sa");
+        assert_eq!(make_labels(1000).len(), 26 * 26);
+    }
+}
@@ -1,8 +1,12 @@
 mod frontend;
+mod jump_labels;
 mod screen_buf;
+mod screen_map;
 mod terminal;
 
-pub use frontend::{Event, Frontend, Key};
+pub use frontend::{Event, Frontend, Key, MouseButton, MouseEvent};
+pub use jump_labels::{JumpLabels, JumpTargetKind};
+pub use screen_map::{RecordingWindow, ScreenMap};
 pub use terminal::Terminal;
 
 use crate::util::{error, SynlessError};
@@ -0,0 +1,156 @@
+use crate::style::Style;
+use crate::tree::NodeId;
+use partial_pretty_printer::pane::PrettyWindow;
+use partial_pretty_printer::{Col, Pos, Row, Size};
+use std::collections::HashMap;
+
+/// Records which node printed each character on the screen, so that a screen position (like a
+/// mouse click) can be mapped back to a node.
+#[derive(Debug, Default)]
+pub struct ScreenMap {
+    /// The second column of a full-width character is recorded with no character.
+    cells: HashMap<(Row, Col), (NodeId, Option<char>)>,
+}
+
+impl ScreenMap {
+    pub fn new() -> ScreenMap {
+        ScreenMap::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// The innermost node that printed the character at `pos`, if any.
+    pub fn node_at(&self, pos: Pos) -> Option<NodeId> {
+        self.cells.get(&(pos.row, pos.col)).map(|(id, _)| *id)
+    }
+
+    /// For each node that printed something, the position of the first character it printed
+    /// (not counting what its descendants printed).
+    pub fn first_positions(&self) -> HashMap<NodeId, Pos> {
+        let mut positions: HashMap<NodeId, Pos> = HashMap::new();
+        for ((row, col), (id, _)) in &self.cells {
+            let pos = Pos {
+                row: *row,
+                col: *col,
+            };
+            positions
+                .entry(*id)
+                .and_modify(|first| {
+                    if (pos.row, pos.col) < (first.row, first.col) {
+                        *first = pos;
+                    }
+                })
+                .or_insert(pos);
+        }
+        positions
+    }
+
+    /// If `pos` is on a character of `text`, as printed by the node at `pos`, returns the index of
+    /// that character in `text`. Only looks at the line containing `pos`.
+    pub fn char_index_at(&self, pos: Pos, text: &str) -> Option<usize> {
+        let id = self.node_at(pos)?;
+        let same_node = |col: Col| match self.cells.get(&(pos.row, col)) {
+            Some((cell_id, _)) => *cell_id == id,
+            None => false,
+        };
+
+        // Find the run of characters printed by this node that contains `pos`.
+        let mut col = pos.col;
+        while col > 0 && same_node(col - 1) {
+            col -= 1;
+        }
+        let mut run = Vec::new();
+        let mut click_index = 0;
+        while same_node(col) {
+            if let Some(ch) = self.cells[&(pos.row, col)].1 {
+                run.push(ch);
+            }
+            if col == pos.col {
+                click_index = run.len().saturating_sub(1);
+            }
+            col += 1;
+        }
+
+        find_text_in_run(&run, click_index, text)
+    }
+}
+
+/// The node's text is somewhere in the `run` of characters it printed, maybe surrounded by
+/// punctuation that the node also printed. Find the occurrence of the text that contains the
+/// character at `click_index`, and return the offset of the click into it.
+fn find_text_in_run(run: &[char], click_index: usize, text: &str) -> Option<usize> {
+    let text = text.chars().collect::<Vec<_>>();
+    if text.is_empty() || text.len() > run.len() {
+        return None;
+    }
+    (0..=run.len() - text.len())
+        .filter(|i| run[*i..*i + text.len()] == text[..])
+        .find(|i| (*i..*i + text.len()).contains(&click_index))
+        .map(|i| click_index - i)
+}
+
+/// A window that records a `ScreenMap` of everything displayed to it, and passes it on to an
+/// underlying window.
+pub struct RecordingWindow<'w, W: PrettyWindow<Style = Style>> {
+    window: &'w mut W,
+    screen_map: &'w mut ScreenMap,
+}
+
+impl<'w, W: PrettyWindow<Style = Style>> RecordingWindow<'w, W> {
+    /// Clears the `screen_map`, and then records into it.
+    pub fn new(window: &'w mut W, screen_map: &'w mut ScreenMap) -> RecordingWindow<'w, W> {
+        screen_map.clear();
+        RecordingWindow { window, screen_map }
+    }
+}
+
+impl<'w, W: PrettyWindow<Style = Style>> PrettyWindow for RecordingWindow<'w, W> {
+    type Error = W::Error;
+    type Style = Style;
+
+    fn size(&self) -> Result<Size, Self::Error> {
+        self.window.size()
+    }
+
+    fn display_char(
+        &mut self,
+        ch: char,
+        pos: Pos,
+        style: &Self::Style,
+        full_width: bool,
+    ) -> Result<(), Self::Error> {
+        if let Some(id) = style.node_id {
+            let cells = &mut self.screen_map.cells;
+            cells.insert((pos.row, pos.col), (id, Some(ch)));
+            if full_width {
+                cells.insert((pos.row, pos.col + 1), (id, None));
+            }
+        }
+        self.window.display_char(ch, pos, style, full_width)
+    }
+
+    fn set_focus(&mut self, pos: Pos) -> Result<(), Self::Error> {
+        self.window.set_focus(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_text_in_run() {
+        let run = "\"key\": \"key\"".chars().collect::<Vec<_>>();
+        // On the opening quote
+        assert_eq!(find_text_in_run(&run, 0, "key"), None);
+        assert_eq!(find_text_in_run(&run, 1, "key"), Some(0));
+        assert_eq!(find_text_in_run(&run, 3, "key"), Some(2));
+        assert_eq!(find_text_in_run(&run, 4, "key"), None);
+        // The second occurrence
+        assert_eq!(find_text_in_run(&run, 9, "key"), Some(1));
+        assert_eq!(find_text_in_run(&run, 1, ""), None);
+        assert_eq!(find_text_in_run(&run, 1, "a much longer text"), None);
+    }
+}
@@ -69,6 +69,8 @@ impl Terminal {
         enable_raw_mode()?;
         stdout()
             .queue(EnterAlternateScreen)?
+            .queue(ct_event::EnableBracketedPaste)?
+            .queue(ct_event::EnableMouseCapture)?
             .queue(cursor::SetCursorStyle::SteadyBar)?
             .queue(cursor::Hide)?;
         stdout().flush()
@@ -78,6 +80,8 @@ impl Terminal {
     fn exit(&mut self) -> Result<(), io::Error> {
         disable_raw_mode()?;
         stdout()
+            .queue(ct_event::DisableBracketedPaste)?
+            .queue(ct_event::DisableMouseCapture)?
             .queue(LeaveAlternateScreen)?
             .queue(cursor::SetCursorStyle::DefaultUserShape)?
             .queue(cursor::Show)?
@@ -200,6 +204,35 @@ impl Frontend for Terminal {
         out.flush()?;
         Ok(())
     }
+
+    /// Uses the OSC 52 escape sequence, which many (but not all) terminal emulators support.
+    fn set_clipboard(&mut self, text: &str) -> Result<(), TerminalError> {
+        let mut out = stdout().lock();
+        write!(out, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Standard base64 encoding, with padding.
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | ((*byte as u32) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0b111111;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
 }
 
 impl Drop for Terminal {
@@ -333,3 +366,17 @@ impl TryInto<KeyModifiers> for ct_event::KeyModifiers {
         Ok(mods)
     }
 }
+
+#[cfg(test)]
+mod terminal_tests {
+    use super::base64_encode;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"M"), "TQ==");
+        assert_eq!(base64_encode(b"Ma"), "TWE=");
+        assert_eq!(base64_encode(b"Man"), "TWFu");
+        assert_eq!(base64_encode("[1, ☐]".as_bytes()), "WzEsIOKYkF0=");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::frontends::Key;
+use crate::util::{error, SynlessError};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A recorded sequence of keypresses, that can be replayed. Written as a string, it's the keys
+/// separated by spaces, like `"j j C-s"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMacro(Vec<Key>);
+
+impl KeyMacro {
+    pub fn keys(&self) -> &[Key] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for KeyMacro {
+    type Err = SynlessError;
+
+    fn from_str(s: &str) -> Result<KeyMacro, SynlessError> {
+        s.split_whitespace()
+            .map(|word| {
+                Key::from_str(word).map_err(|_| error!(Keymap, "Invalid key '{}' in macro", word))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(KeyMacro)
+    }
+}
+
+impl fmt::Display for KeyMacro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, key) in self.0.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records keyboard macros, and keeps them in named registers.
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    macros: HashMap<char, KeyMacro>,
+    /// The register being recorded into, and the keys recorded so far.
+    recording: Option<(char, KeyMacro)>,
+    /// How many keys had been recorded when the current action started.
+    action_start: usize,
+}
+
+impl MacroRecorder {
+    pub fn new() -> MacroRecorder {
+        MacroRecorder::default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start_recording(&mut self, name: char) -> Result<(), SynlessError> {
+        if let Some((recording_name, _)) = &self.recording {
+            return Err(error!(
+                Keymap,
+                "Already recording a macro into register '{}'", recording_name
+            ));
+        }
+        self.recording = Some((name, KeyMacro::default()));
+        self.action_start = 0;
+        Ok(())
+    }
+
+    /// Mark the start of an action: a keypress, plus any menu selections made after it until the
+    /// menu closes.
+    pub fn start_action(&mut self) {
+        if let Some((_, key_macro)) = &self.recording {
+            self.action_start = key_macro.len();
+        }
+    }
+
+    /// Stop recording, and save the macro. The keys of the current action are dropped, since
+    /// they're what stopped the recording. Returns the name of the register the macro was saved
+    /// in.
+    pub fn stop_recording(&mut self) -> Result<char, SynlessError> {
+        let (name, mut key_macro) = self
+            .recording
+            .take()
+            .ok_or_else(|| error!(Keymap, "Not recording a macro"))?;
+        key_macro.0.truncate(self.action_start);
+        self.macros.insert(name, key_macro);
+        Ok(name)
+    }
+
+    /// Record a keypress, if recording.
+    pub fn record(&mut self, key: Key) {
+        if let Some((_, key_macro)) = &mut self.recording {
+            key_macro.0.push(key);
+        }
+    }
+
+    pub fn get(&self, name: char) -> Option<&KeyMacro> {
+        self.macros.get(&name)
+    }
+
+    pub fn set(&mut self, name: char, key_macro: KeyMacro) {
+        self.macros.insert(name, key_macro);
+    }
+
+    /// The names of all saved macros, in order.
+    pub fn names(&self) -> Vec<char> {
+        let mut names = self.macros.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+}
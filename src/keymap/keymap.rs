@@ -89,6 +89,13 @@ pub struct KeyProg {
     prog: rhai::FnPtr,
 }
 
+impl KeyProg {
+    /// Whether the menu is closed before running the program.
+    pub fn close_menu(&self) -> bool {
+        self.close_menu
+    }
+}
+
 impl KeyProgSpec {
     // If this KeyProgSpec is from a general binding, `candidate` should be None.
     fn to_key_prog(&self, candidate: Option<&Candidate>) -> KeyProg {
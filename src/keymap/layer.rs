@@ -249,6 +249,14 @@ impl LayerManager {
         self.active_menu.as_ref().map(|menu| menu.description())
     }
 
+    pub fn menu_name(&self) -> Option<&str> {
+        self.active_menu.as_ref().map(|menu| menu.name())
+    }
+
+    pub fn menu_input(&self) -> Option<&str> {
+        self.active_menu.as_ref().and_then(|menu| menu.input())
+    }
+
     /*********
      * Input *
      *********/
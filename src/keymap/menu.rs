@@ -14,6 +14,8 @@ pub enum MenuSelectionCmd {
     Down,
     Backspace,
     Insert(char),
+    /// Select the candidate at this index.
+    Select(usize),
 }
 
 /// An open menu. Keeps track of the state of its candidate selection.
@@ -55,7 +57,7 @@ impl MenuSelection {
     }
 
     fn execute(&mut self, cmd: MenuSelectionCmd) {
-        use MenuSelectionCmd::{Backspace, Down, Insert, Up};
+        use MenuSelectionCmd::{Backspace, Down, Insert, Select, Up};
 
         match cmd {
             Up => self.index = self.index.saturating_sub(1),
@@ -80,6 +82,11 @@ impl MenuSelection {
                 self.index = self.default_index;
                 self.update_filtered_candidates();
             }
+            Select(index) => {
+                if index < self.filtered_candidates.len() {
+                    self.index = index;
+                }
+            }
         }
     }
 
@@ -146,10 +153,21 @@ impl Menu {
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn description(&self) -> &str {
         &self.description
     }
 
+    /// The text typed into the menu's candidate selection, if it has one.
+    pub fn input(&self) -> Option<&str> {
+        self.selection
+            .as_ref()
+            .map(|selection| selection.input.as_str())
+    }
+
     #[must_use]
     pub fn execute(&mut self, cmd: MenuSelectionCmd) -> bool {
         if let Some(selection) = &mut self.selection {
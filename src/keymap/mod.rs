@@ -1,7 +1,9 @@
+mod key_macro;
 mod keymap;
 mod layer;
 mod menu;
 
+pub use key_macro::{KeyMacro, MacroRecorder};
 pub use keymap::{KeyProg, Keymap};
 pub use layer::{KeyLookupResult, Layer, LayerManager};
 pub use menu::MenuSelectionCmd;
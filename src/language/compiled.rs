@@ -2,7 +2,7 @@ use super::specs::{
     AritySpec, ConstructSpec, GrammarSpec, LanguageSpec, NotationSetSpec, SortSpec,
 };
 use crate::language::LanguageError;
-use crate::style::{StyleLabel, ValidNotation};
+use crate::style::{Notation, StyleLabel, ValidNotation};
 use crate::util::{IndexedMap, SynlessBug};
 use bit_set::BitSet;
 use partial_pretty_printer as ppp;
@@ -58,6 +58,7 @@ pub struct LanguageCompiled {
     pub display_notation: NotationSetId,
     /// Load files with these extensions using this language. Must include the `.`.
     pub file_extensions: Vec<String>,
+    pub trim_blank_lines: bool,
 }
 
 #[derive(Debug)]
@@ -65,6 +66,8 @@ pub struct NotationSetCompiled {
     pub name: String,
     /// ConstructId -> ValidNotation
     pub notations: Vec<ValidNotation>,
+    /// ConstructId -> Notation, as written in the spec. Used to derive a parser from the notation.
+    pub raw_notations: Vec<Notation>,
 }
 
 pub fn compile_language(language_spec: LanguageSpec) -> Result<LanguageCompiled, LanguageError> {
@@ -99,6 +102,7 @@ pub fn compile_language(language_spec: LanguageSpec) -> Result<LanguageCompiled,
         source_notation,
         display_notation,
         file_extensions: language_spec.file_extensions,
+        trim_blank_lines: language_spec.trim_blank_lines,
     })
 }
 
@@ -133,9 +137,11 @@ pub(super) fn compile_notation_set(
     // Look up the notation of every construct in the grammar,
     // putting them in a Vec ordered by ConstructId.
     let mut notations = Vec::new();
+    let mut raw_notations = Vec::new();
     for id in &grammar.constructs {
         let construct = &grammar.constructs[id];
         if let Some(notation) = notations_map.remove(&construct.name) {
+            raw_notations.push(notation.clone());
             let valid_notation = notation.validate().map_err(|err| {
                 LanguageError::InvalidNotation(
                     notation_set.name.clone(),
@@ -163,6 +169,7 @@ pub(super) fn compile_notation_set(
     Ok(NotationSetCompiled {
         name: notation_set.name,
         notations,
+        raw_notations,
     })
 }
 
@@ -5,7 +5,7 @@ use super::compiled::{
 use super::specs::NotationSetSpec;
 use super::storage::Storage;
 use super::LanguageError;
-use crate::style::ValidNotation;
+use crate::style::{Notation, ValidNotation};
 use crate::util::bug;
 
 // NOTE: Why all the wrapper types, instead of using indexes? Two reasons:
@@ -126,6 +126,10 @@ impl Language {
         }
     }
 
+    pub fn trims_blank_lines(self, s: &Storage) -> bool {
+        s.languages[self.language].trim_blank_lines
+    }
+
     pub fn construct(self, s: &Storage, construct_name: &str) -> Option<Construct> {
         let construct = grammar(s, self.language).constructs.id(construct_name)?;
         Some(Construct {
@@ -224,6 +228,15 @@ impl NotationSet {
         }
         &s.languages[self.language].notation_sets[self.notation_set].notations[construct.construct]
     }
+
+    /// The notation for `construct`, as it was written in the language spec (before validation).
+    pub fn raw_notation(self, s: &Storage, construct: Construct) -> &Notation {
+        if self.language != construct.language {
+            bug!("NotationSet::raw_notation - language mismatch");
+        }
+        &s.languages[self.language].notation_sets[self.notation_set].raw_notations
+            [construct.construct]
+    }
 }
 
 impl Sort {
@@ -7,7 +7,7 @@ use crate::util::{error, SynlessError};
 use partial_pretty_printer as ppp;
 use std::fmt;
 
-pub use interface::{Arity, Construct, Language};
+pub use interface::{Arity, Construct, Language, Sort};
 pub use specs::{AritySpec, ConstructSpec, GrammarSpec, LanguageSpec, NotationSetSpec, SortSpec};
 pub use storage::Storage;
 
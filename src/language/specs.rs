@@ -65,4 +65,8 @@ pub struct LanguageSpec {
     pub default_source_notation: Option<String>,
     /// Load files with these extensions using this language. Must include the `.`.
     pub file_extensions: Vec<String>,
+    /// When printing source, remove the indentation from lines that would otherwise be blank,
+    /// like the lines of blank-line nodes.
+    #[serde(default)]
+    pub trim_blank_lines: bool,
 }
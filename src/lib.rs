@@ -13,15 +13,18 @@ mod util;
 
 pub mod parsing;
 
-pub use engine::{DocName, Engine, Settings};
-pub use frontends::Terminal;
-pub use keymap::{KeyProg, Keymap, Layer};
+pub use engine::{
+    BookmarkCommand, ClipboardCommand, DocName, Engine, Settings, TextEdCommand, TextNavCommand,
+    TreeEdCommand, TreeNavCommand,
+};
+pub use frontends::{Event, Frontend, Key, Terminal};
+pub use keymap::{KeyMacro, KeyProg, Keymap, Layer};
 pub use language::{
     AritySpec, Construct, ConstructSpec, GrammarSpec, Language, LanguageSpec, NotationSetSpec,
     SortSpec, Storage,
 };
 pub use pretty_doc::DocRef;
 pub use runtime::Runtime;
-pub use style::ColorTheme;
+pub use style::{ColorTheme, Style};
 pub use tree::{Location, Node};
 pub use util::{Log, LogEntry, LogLevel, SynlessBug, SynlessError};
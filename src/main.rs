@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::panic;
+use std::panic::{self, AssertUnwindSafe};
 use std::rc::Rc;
 use synless::{log, ColorTheme, Log, Runtime, Settings, SynlessBug, SynlessError, Terminal};
 
@@ -46,7 +46,7 @@ fn make_runtime() -> Rc<RefCell<Runtime<Terminal>>> {
     Rc::new(RefCell::new(runtime))
 }
 
-fn run() -> Result<(), Box<rhai::EvalAltResult>> {
+fn run(runtime: Rc<RefCell<Runtime<Terminal>>>) -> Result<(), Box<rhai::EvalAltResult>> {
     // TODO: Log which rhai script failed to compile (instead of simple ?s)
     let mut engine = make_engine();
 
@@ -63,7 +63,6 @@ fn run() -> Result<(), Box<rhai::EvalAltResult>> {
     };
 
     // Register runtime methods into internals_module and base_module
-    let runtime = make_runtime();
     Runtime::register_internal_methods(runtime.clone(), &mut internals_mod);
     engine.register_static_module("synless_internals", internals_mod.into());
     Runtime::register_external_methods(runtime, &mut base_mod);
@@ -116,12 +115,22 @@ fn main() {
         log!(Error, "{message}")
     }));
 
-    // Run the editor, catching any panics, then print the log.
-    let _ = panic::catch_unwind(|| {
-        if let Err(err) = run() {
-            display_error(err);
+    // Run the editor, catching any panics. If it panics, try to save recovery files for any
+    // modified docs. Then drop the runtime (restoring the terminal) and print the log.
+    if let Ok(runtime) = panic::catch_unwind(make_runtime) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Err(err) = run(runtime.clone()) {
+                display_error(err);
+            }
+        }));
+        if result.is_err() {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                if let Ok(mut runtime) = runtime.try_borrow_mut() {
+                    runtime.prepare_to_abort();
+                }
+            }));
         }
-    });
+    }
     panic::set_hook(old_hook);
     println!("{}", Log::to_string());
 }
@@ -1,15 +1,22 @@
-use super::{Parse, ParseError};
-use crate::language::{Language, Storage};
+use super::{offset_to_pos, Parse, ParseError};
+use crate::language::{Construct, Language, Storage};
 use crate::tree::Node;
 use crate::util::{bug_assert, error, SynlessBug, SynlessError};
-use partial_pretty_printer as ppp;
 
 const LANGUAGE_NAME: &str = "json";
 const PARSER_NAME: &str = "builtin_json_parser";
+const JSONC_PARSER_NAME: &str = "builtin_jsonc_parser";
+const JSONC_EXTENSION: &str = ".jsonc";
 
+/// Parses strict json, except in files whose name ends in `.jsonc`, which are parsed as JSONC
+/// (json with comments and trailing commas).
 #[derive(Debug)]
 pub struct JsonParser;
 
+/// Parses JSONC (json with comments and trailing commas), whatever the file name.
+#[derive(Debug)]
+pub struct JsoncParser;
+
 impl Parse for JsonParser {
     fn name(&self) -> &str {
         PARSER_NAME
@@ -21,83 +28,556 @@ impl Parse for JsonParser {
         file_name: &str,
         source: &str,
     ) -> Result<Node, SynlessError> {
-        // Serde json uses 1-indexed positions; we use 0-indexed positions.
-        let json = serde_json::from_str(source).map_err(|err| ParseError {
-            pos: Some(ppp::Pos {
-                row: (err.line() as ppp::Row).saturating_sub(1),
-                col: (err.column() as ppp::Col).saturating_sub(1),
-            }),
-            file_name: file_name.to_owned(),
-            message: format!("{}", err),
-        })?;
-
-        let json_lang = s.language(LANGUAGE_NAME)?;
-        let json_node = json_to_node(s, json, json_lang).map_err(|construct| {
-            error!(
-                Parse,
-                "Construct '{}' missing from json language spec", construct
-            )
-        })?;
-        let root_node = Node::with_children(s, json_lang.root_construct(s), [json_node])
-            .ok_or_else(|| error!(Parse, "Bug in json parser: root node arity mismatch"))?;
-        Ok(root_node)
+        let result = self.parse_with_recovery(s, file_name, source)?;
+        fail_on_first_error(s, result)
+    }
+
+    fn parse_with_recovery(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<(Node, Vec<ParseError>), SynlessError> {
+        let jsonc = file_name.ends_with(JSONC_EXTENSION);
+        parse_tolerantly(s, file_name, source, jsonc)
+    }
+}
+
+impl Parse for JsoncParser {
+    fn name(&self) -> &str {
+        JSONC_PARSER_NAME
+    }
+
+    fn parse(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<Node, SynlessError> {
+        let result = self.parse_with_recovery(s, file_name, source)?;
+        fail_on_first_error(s, result)
+    }
+
+    fn parse_with_recovery(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<(Node, Vec<ParseError>), SynlessError> {
+        parse_tolerantly(s, file_name, source, true)
     }
 }
 
-fn json_to_node(
+fn parse_tolerantly(
     s: &mut Storage,
-    json: serde_json::Value,
-    json_lang: Language,
-) -> Result<Node, &'static str> {
-    use serde_json::Value::{Array, Bool, Null, Number, Object, String};
-
-    let make_node = |s: &mut Storage, construct_name: &'static str| -> Result<Node, &'static str> {
-        let construct = json_lang
-            .construct(s, construct_name)
-            .ok_or(construct_name)?;
-        Ok(Node::new(s, construct))
+    file_name: &str,
+    source: &str,
+    jsonc: bool,
+) -> Result<(Node, Vec<ParseError>), SynlessError> {
+    let constructs = JsonConstructs::new(s)?;
+    let mut parser = TolerantParser {
+        constructs,
+        jsonc,
+        file_name,
+        source,
+        pos: 0,
+        errors: Vec::new(),
     };
+    let root = parser.parse_root(s);
+    Ok((root, parser.errors))
+}
+
+fn fail_on_first_error(
+    s: &mut Storage,
+    (root, errors): (Node, Vec<ParseError>),
+) -> Result<Node, SynlessError> {
+    if let Some(error) = errors.into_iter().next() {
+        root.delete_root(s);
+        return Err(error.into());
+    }
+    Ok(root)
+}
+
+/*******************
+ * Tolerant Parser *
+ *******************/
+
+struct JsonConstructs {
+    language: Language,
+    root: Construct,
+    null: Construct,
+    true_: Construct,
+    false_: Construct,
+    string: Construct,
+    number: Construct,
+    array: Construct,
+    key: Construct,
+    object_pair: Construct,
+    object: Construct,
+    line_comment: Construct,
+    block_comment: Construct,
+    blank_line: Construct,
+}
+
+impl JsonConstructs {
+    fn new(s: &Storage) -> Result<JsonConstructs, SynlessError> {
+        let language = s.language(LANGUAGE_NAME)?;
+        let construct = |name: &str| {
+            language.construct(s, name).ok_or_else(|| {
+                error!(
+                    Parse,
+                    "Construct '{}' missing from json language spec", name
+                )
+            })
+        };
+        Ok(JsonConstructs {
+            language,
+            root: language.root_construct(s),
+            null: construct("Null")?,
+            true_: construct("True")?,
+            false_: construct("False")?,
+            string: construct("String")?,
+            number: construct("Number")?,
+            array: construct("Array")?,
+            key: construct("Key")?,
+            object_pair: construct("ObjectPair")?,
+            object: construct("Object")?,
+            line_comment: construct("LineComment")?,
+            block_comment: construct("BlockComment")?,
+            blank_line: construct("BlankLine")?,
+        })
+    }
+}
+
+/// A hand-written json parser that never fails. Malformed values become holes if they're empty,
+/// and otherwise `Number` nodes containing the raw text (which the source notation prints as-is).
+/// Every problem is recorded in `errors`.
+///
+/// Comments and trailing commas are only allowed if `jsonc` is set. (Otherwise they're errors,
+/// but comments are kept anyways.) Comments and blank lines inside an array or object are kept in
+/// place as `is_comment_or_ws` nodes. Comments elsewhere are moved to the nearest place that can
+/// hold them, so they don't round trip exactly: comments around an object pair's `:` go before
+/// the pair, and comments before or after the top-level value go at the start or end of it (or
+/// are errors if it's not an array or object). Likewise, a multi-line block comment becomes one
+/// `LineComment` per non-blank line, because `BlockComment`s are printed on one line.
+struct TolerantParser<'a> {
+    constructs: JsonConstructs,
+    jsonc: bool,
+    file_name: &'a str,
+    source: &'a str,
+    /// Byte offset into `source`.
+    pos: usize,
+    errors: Vec<ParseError>,
+}
 
-    match json {
-        Null => make_node(s, "Null"),
-        Bool(false) => make_node(s, "False"),
-        Bool(true) => make_node(s, "True"),
-        String(string) => {
-            let node = make_node(s, "String")?;
-            node.text_mut(s).unwrap().set(string);
-            Ok(node)
+impl<'a> TolerantParser<'a> {
+    fn parse_root(&mut self, s: &mut Storage) -> Node {
+        let leading_comments = self.skip_trivia(s, false);
+        let value = self
+            .parse_value_or_placeholder(s)
+            .unwrap_or_else(|| Node::new_hole(s, self.constructs.language));
+        let trailing_comments = self.skip_trivia(s, false);
+        if self.pos < self.source.len() {
+            self.error(
+                self.pos,
+                "Unexpected input after the end of the document".to_owned(),
+            );
         }
-        Number(n) => {
-            let node = make_node(s, "Number")?;
-            node.text_mut(s).unwrap().set(n.to_string());
-            Ok(node)
+        let construct = value.construct(s);
+        if construct == self.constructs.array || construct == self.constructs.object {
+            for (_, comment) in leading_comments.into_iter().rev() {
+                bug_assert!(
+                    value.insert_first_child(s, comment),
+                    "Wrong arity for json comment"
+                );
+            }
+            self.insert_trivia(s, value, trailing_comments);
+        } else {
+            for (pos, comment) in leading_comments.into_iter().chain(trailing_comments) {
+                self.error(
+                    pos,
+                    "Comments are only allowed inside an array or object".to_owned(),
+                );
+                comment.delete_root(s);
+            }
+        }
+        Node::with_children(s, self.constructs.root, [value]).bug()
+    }
+
+    /// Parse a value. If it's malformed, skip past it and return a placeholder containing its raw
+    /// text instead, or `None` if there was no text.
+    fn parse_value_or_placeholder(&mut self, s: &mut Storage) -> Option<Node> {
+        if let Some(node) = self.parse_value(s) {
+            return Some(node);
+        }
+        let start = self.pos;
+        let raw = self.skip_until(|ch| matches!(ch, ',' | ']' | '}'));
+        if raw.is_empty() {
+            self.error(start, "Expected a value".to_owned());
+            None
+        } else {
+            self.error(start, format!("Invalid value '{}'", raw));
+            Some(Node::with_text(s, self.constructs.number, raw.to_owned()).bug())
         }
-        Array(array) => {
-            let node = make_node(s, "Array")?;
-            for value in array {
-                let child = json_to_node(s, value, json_lang)?;
+    }
+
+    /// Parse a well-formed value (though its descendants may be malformed). Returns `None`
+    /// without consuming any input if there isn't one.
+    fn parse_value(&mut self, s: &mut Storage) -> Option<Node> {
+        let rest = self.rest();
+        let keyword_is = |keyword: &str| {
+            rest.starts_with(keyword)
+                && !rest[keyword.len()..].starts_with(|ch: char| ch.is_alphanumeric())
+        };
+        let c = &self.constructs;
+        let node = match rest.chars().next()? {
+            '{' => self.parse_object(s),
+            '[' => self.parse_array(s),
+            '"' => {
+                let string = self.parse_string();
+                Node::with_text(s, self.constructs.string, string).bug()
+            }
+            '-' | '0'..='9' => {
+                let start = self.pos;
+                let number =
+                    self.skip_until(|ch| !matches!(ch, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'));
+                if serde_json::from_str::<serde_json::Number>(number).is_err() {
+                    self.error(start, format!("Invalid number '{}'", number));
+                }
+                Node::with_text(s, self.constructs.number, number.to_owned()).bug()
+            }
+            _ if keyword_is("null") => {
+                self.pos += "null".len();
+                Node::new(s, c.null)
+            }
+            _ if keyword_is("true") => {
+                self.pos += "true".len();
+                Node::new(s, c.true_)
+            }
+            _ if keyword_is("false") => {
+                self.pos += "false".len();
+                Node::new(s, c.false_)
+            }
+            _ => return None,
+        };
+        Some(node)
+    }
+
+    fn parse_array(&mut self, s: &mut Storage) -> Node {
+        let array = Node::new(s, self.constructs.array);
+        let open_pos = self.pos;
+        self.pos += 1;
+        loop {
+            let trivia = self.skip_trivia(s, true);
+            self.insert_trivia(s, array, trivia);
+            match self.peek() {
+                Some(']') => {
+                    self.pos += 1;
+                    return array;
+                }
+                Some('}') => {
+                    self.error(self.pos, "Expected ']'".to_owned());
+                    return array;
+                }
+                None => {
+                    self.error(open_pos, "Unclosed '['".to_owned());
+                    return array;
+                }
+                _ => (),
+            }
+            if let Some(elem) = self.parse_value_or_placeholder(s) {
                 bug_assert!(
-                    node.insert_last_child(s, child),
+                    array.insert_last_child(s, elem),
                     "Wrong arity in json Array"
                 );
             }
-            Ok(node)
+            self.skip_separator(s, array, ']');
         }
-        Object(object) => {
-            let node = make_node(s, "Object")?;
-            for (key, value) in object {
-                let key_node = make_node(s, "Key")?;
-                key_node.text_mut(s).unwrap().set(key);
-                let value_node = json_to_node(s, value, json_lang)?;
-                let pair_construct = json_lang.construct(s, "ObjectPair").ok_or("ObjectPair")?;
-                let child = Node::with_children(s, pair_construct, [key_node, value_node])
-                    .bug_msg("Wrong arity in json ObjectPair");
+    }
+
+    fn parse_object(&mut self, s: &mut Storage) -> Node {
+        let object = Node::new(s, self.constructs.object);
+        let open_pos = self.pos;
+        self.pos += 1;
+        loop {
+            let trivia = self.skip_trivia(s, true);
+            self.insert_trivia(s, object, trivia);
+            match self.peek() {
+                Some('}') => {
+                    self.pos += 1;
+                    return object;
+                }
+                Some(']') => {
+                    self.error(self.pos, "Expected '}'".to_owned());
+                    return object;
+                }
+                None => {
+                    self.error(open_pos, "Unclosed '{'".to_owned());
+                    return object;
+                }
+                _ => (),
+            }
+            if let Some(pair) = self.parse_object_pair(s, object) {
                 bug_assert!(
-                    node.insert_last_child(s, child),
+                    object.insert_last_child(s, pair),
                     "Wrong arity in json Object"
                 );
             }
-            Ok(node)
+            self.skip_separator(s, object, '}');
+        }
+    }
+
+    /// Parse a key-value pair. Any comments inside it are added to the end of `object`, since
+    /// they'll precede the pair.
+    fn parse_object_pair(&mut self, s: &mut Storage, object: Node) -> Option<Node> {
+        let c = &self.constructs;
+        let (language, c_key, c_pair) = (c.language, c.key, c.object_pair);
+
+        let key_pos = self.pos;
+        let key = if self.peek() == Some('"') {
+            let key = self.parse_string();
+            Node::with_text(s, c_key, key).bug()
+        } else {
+            let raw = self.skip_until(|ch| matches!(ch, ':' | ',' | ']' | '}'));
+            if raw.is_empty() {
+                self.error(key_pos, "Expected a key".to_owned());
+                if self.peek() != Some(':') {
+                    return None;
+                }
+                Node::new_hole(s, language)
+            } else {
+                self.error(key_pos, "Expected a quoted key".to_owned());
+                Node::with_text(s, c_key, raw.to_owned()).bug()
+            }
+        };
+
+        let comments = self.skip_trivia(s, false);
+        self.insert_trivia(s, object, comments);
+        let value = if self.peek() == Some(':') {
+            self.pos += 1;
+            let comments = self.skip_trivia(s, false);
+            self.insert_trivia(s, object, comments);
+            self.parse_value_or_placeholder(s)
+        } else {
+            self.error(self.pos, "Expected ':'".to_owned());
+            None
+        };
+        let value = value.unwrap_or_else(|| Node::new_hole(s, language));
+        Some(Node::with_children(s, c_pair, [key, value]).bug())
+    }
+
+    /// Parse a quoted string, processing escapes. An unterminated string ends at the end of the
+    /// line.
+    fn parse_string(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        let mut string = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            let Some(ch) = chars.next() else {
+                self.error(start, "Unterminated string".to_owned());
+                break;
+            };
+            if ch == '\n' {
+                self.error(start, "Unterminated string".to_owned());
+                break;
+            }
+            self.pos += ch.len_utf8();
+            match ch {
+                '"' => break,
+                '\\' => {
+                    let Some(escaped) = chars.next() else {
+                        continue;
+                    };
+                    self.pos += escaped.len_utf8();
+                    match escaped {
+                        '"' | '\\' | '/' => string.push(escaped),
+                        'b' => string.push('\u{8}'),
+                        'f' => string.push('\u{c}'),
+                        'n' => string.push('\n'),
+                        'r' => string.push('\r'),
+                        't' => string.push('\t'),
+                        'u' => {
+                            let hex = take_hex_digits(&mut chars);
+                            self.pos += hex.len();
+                            let mut code = parse_hex_escape(&hex);
+                            if let Some(high @ 0xD800..=0xDBFF) = code {
+                                // Characters outside the BMP are written as a UTF-16 surrogate
+                                // pair: a high surrogate escape, then a low surrogate escape.
+                                let mut lookahead = chars.clone();
+                                let low = match (lookahead.next(), lookahead.next()) {
+                                    (Some('\\'), Some('u')) => {
+                                        parse_hex_escape(&take_hex_digits(&mut lookahead))
+                                    }
+                                    _ => None,
+                                };
+                                code = match low {
+                                    Some(low @ 0xDC00..=0xDFFF) => {
+                                        chars = lookahead;
+                                        self.pos += "\\u".len() + 4;
+                                        Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                                    }
+                                    _ => None,
+                                };
+                            }
+                            match code.and_then(char::from_u32) {
+                                Some(ch) => string.push(ch),
+                                None => {
+                                    self.error(self.pos, format!("Invalid escape '\\u{hex}'"));
+                                    string.push_str("\\u");
+                                    string.push_str(&hex);
+                                }
+                            }
+                        }
+                        _ => {
+                            self.error(self.pos, format!("Invalid escape '\\{escaped}'"));
+                            string.push('\\');
+                            string.push(escaped);
+                        }
+                    }
+                }
+                _ => string.push(ch),
+            }
+        }
+        string
+    }
+
+    /// After an element of an array or object, skip the `,` that should follow it (unless the
+    /// element was the last one).
+    fn skip_separator(&mut self, s: &mut Storage, list: Node, close: char) {
+        let trivia = self.skip_trivia(s, true);
+        self.insert_trivia(s, list, trivia);
+        match self.peek() {
+            Some(',') => {
+                let comma_pos = self.pos;
+                self.pos += 1;
+                let trivia = self.skip_trivia(s, true);
+                self.insert_trivia(s, list, trivia);
+                if !self.jsonc && matches!(self.peek(), Some(']' | '}')) {
+                    self.error(
+                        comma_pos,
+                        "Trailing commas are only allowed in JSONC".to_owned(),
+                    );
+                }
+            }
+            Some(ch) if ch == close || matches!(ch, ']' | '}') => (),
+            None => (),
+            Some(_) => self.error(self.pos, format!("Expected ',' or '{}'", close)),
+        }
+    }
+
+    /// Skip until just before the first char matching `stop` (or the end of input), and return
+    /// the skipped text with trailing whitespace removed.
+    fn skip_until(&mut self, stop: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(stop).unwrap_or(rest.len());
+        self.pos += len;
+        rest[..len].trim_end()
+    }
+
+    /// Skip whitespace and comments, and return them as `is_comment_or_ws` nodes paired with
+    /// their position. Blank lines are only included if `keep_blank_lines` is set.
+    fn skip_trivia(&mut self, s: &mut Storage, keep_blank_lines: bool) -> Vec<(usize, Node)> {
+        let c = &self.constructs;
+        let (line_comment, block_comment, blank_line) =
+            (c.line_comment, c.block_comment, c.blank_line);
+        let mut trivia = Vec::new();
+        loop {
+            let rest = self.rest();
+            let whitespace = &rest[..rest.len() - rest.trim_start().len()];
+            if keep_blank_lines {
+                let blank_lines = whitespace.matches('\n').count().saturating_sub(1);
+                for _ in 0..blank_lines {
+                    trivia.push((self.pos, Node::new(s, blank_line)));
+                }
+            }
+            self.pos += whitespace.len();
+
+            let start = self.pos;
+            let rest = self.rest();
+            if let Some(comment) = rest.strip_prefix("//") {
+                let text = &comment[..comment.find('\n').unwrap_or(comment.len())];
+                self.pos += "//".len() + text.len();
+                let node = Node::with_text(s, line_comment, text.to_owned()).bug();
+                trivia.push((start, node));
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let text = match comment.find("*/") {
+                    Some(len) => {
+                        self.pos += "/**/".len() + len;
+                        &comment[..len]
+                    }
+                    None => {
+                        self.error(self.pos, "Unterminated comment".to_owned());
+                        self.pos = self.source.len();
+                        comment
+                    }
+                };
+                if text.contains('\n') {
+                    // Block comments are printed on one line, so split multi-line ones
+                    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                        let line = format!(" {}", line);
+                        trivia.push((start, Node::with_text(s, line_comment, line).bug()));
+                    }
+                } else {
+                    let node = Node::with_text(s, block_comment, text.to_owned()).bug();
+                    trivia.push((start, node));
+                }
+            } else {
+                return trivia;
+            }
+            if !self.jsonc {
+                self.error(start, "Comments are only allowed in JSONC".to_owned());
+            }
+        }
+    }
+
+    /// Add each of the `trivia` nodes to the end of `list`.
+    fn insert_trivia(&self, s: &mut Storage, list: Node, trivia: Vec<(usize, Node)>) {
+        for (_, node) in trivia {
+            bug_assert!(
+                list.insert_last_child(s, node),
+                "Wrong arity for json comment"
+            );
         }
     }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn error(&mut self, pos: usize, message: String) {
+        self.errors.push(ParseError {
+            pos: Some(offset_to_pos(self.source, pos)),
+            file_name: self.file_name.to_owned(),
+            message,
+        });
+    }
+}
+
+/// Take the (up to 4) hex digits of a `\u` escape from the start of `chars`.
+fn take_hex_digits(chars: &mut std::str::Chars) -> String {
+    let mut hex = String::new();
+    while hex.len() < 4 {
+        match chars.clone().next() {
+            Some(ch) if ch.is_ascii_hexdigit() => {
+                chars.next();
+                hex.push(ch);
+            }
+            _ => break,
+        }
+    }
+    hex
+}
+
+/// The UTF-16 code unit written by the hex digits of a `\u` escape, if there are all 4 of them.
+fn parse_hex_escape(hex: &str) -> Option<u32> {
+    if hex.len() == 4 {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        None
+    }
 }
@@ -1,4 +1,7 @@
 mod json_parser;
+mod notation_parser;
+mod pattern;
+mod sexpr;
 
 use crate::language::Storage;
 use crate::tree::Node;
@@ -7,7 +10,10 @@ use partial_pretty_printer as ppp;
 use std::fmt;
 use std::path::Path;
 
-pub use json_parser::JsonParser;
+pub use json_parser::{JsonParser, JsoncParser};
+pub use notation_parser::{parse_with_notation, NotationParser};
+pub use pattern::{TreePattern, TreeTemplate};
+pub use sexpr::{parse_sexpr, print_sexpr, SexprParser};
 
 pub trait Parse: fmt::Debug {
     fn name(&self) -> &str;
@@ -18,6 +24,18 @@ pub trait Parse: fmt::Debug {
         file_name: &str,
         source: &str,
     ) -> Result<Node, SynlessError>;
+
+    /// Like `parse`, but if the source is malformed, keep as much of it as possible instead of
+    /// failing, using holes or placeholder nodes where parsing failed. Returns the errors that
+    /// were recovered from. Parsers that don't support recovery fall back to `parse`.
+    fn parse_with_recovery(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<(Node, Vec<ParseError>), SynlessError> {
+        Ok((self.parse(s, file_name, source)?, Vec::new()))
+    }
 }
 
 #[derive(Debug)]
@@ -51,16 +69,31 @@ impl ParseError {
     }
 }
 
-impl From<ParseError> for SynlessError {
-    fn from(error: ParseError) -> SynlessError {
-        let doc = error.file_name;
-        let message = error.message;
-        if let Some(ppp::Pos { row, col }) = error.pos {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let doc = &self.file_name;
+        let message = &self.message;
+        if let Some(ppp::Pos { row, col }) = self.pos {
             let line = row + 1;
             let col = col + 1;
-            error!(Parse, "In {doc} at {line}:{col}: {message}")
+            write!(f, "In {doc} at {line}:{col}: {message}")
         } else {
-            error!(Parse, "In {doc}: {message}")
+            write!(f, "In {doc}: {message}")
         }
     }
 }
+
+impl From<ParseError> for SynlessError {
+    fn from(error: ParseError) -> SynlessError {
+        error!(Parse, "{}", error)
+    }
+}
+
+/// Convert a byte offset into `source` into a (0-indexed) row and column.
+fn offset_to_pos(source: &str, offset: usize) -> ppp::Pos {
+    let before = &source[..offset];
+    let row = before.chars().filter(|ch| *ch == '\n').count() as ppp::Row;
+    let last_line = before.rsplit('\n').next().unwrap_or("");
+    let col = last_line.chars().count() as ppp::Col;
+    ppp::Pos { row, col }
+}
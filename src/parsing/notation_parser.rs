@@ -0,0 +1,833 @@
+//! A parser that works for any language with a source notation, by reading that notation
+//! "backwards". Layout (newlines, indentation, `Choice`s between single and multi-line forms) is
+//! ignored, and whitespace between tokens is skipped. What's left of each notation is a sequence
+//! of literals, text, and children, which is parsed with ordered choice and backtracking.
+//!
+//! To decide which construct a child is, the parser looks at the first token of each construct
+//! that's allowed there. If two constructs in the same sort can start the same way, the grammar
+//! is considered too ambiguous to parse, and an error is given instead.
+//!
+//! Texty nodes end at the literal that follows them in their notation (e.g. the closing `"` of a
+//! string), or, if there isn't one, at the first whitespace or punctuation character. In the first
+//! case, backslash escapes in the text are processed the same way as in json.
+//!
+//! A literal that ends in a letter, digit, or `_` only matches if it isn't followed by another
+//! one, so that e.g. `true` doesn't match the start of `trueish`.
+
+use super::{offset_to_pos, Parse, ParseError};
+use crate::language::{Arity, Construct, Language, Sort, Storage};
+use crate::style::Notation;
+use crate::tree::Node;
+use crate::util::{bug, SynlessBug, SynlessError};
+use partial_pretty_printer as ppp;
+use std::collections::HashSet;
+
+const PARSER_NAME: &str = "builtin_notation_parser";
+
+/// Parses documents in a single language, using that language's source notation.
+#[derive(Debug)]
+pub struct NotationParser {
+    language_name: String,
+}
+
+impl NotationParser {
+    pub fn new(language_name: &str) -> NotationParser {
+        NotationParser {
+            language_name: language_name.to_owned(),
+        }
+    }
+}
+
+impl Parse for NotationParser {
+    fn name(&self) -> &str {
+        PARSER_NAME
+    }
+
+    fn parse(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<Node, SynlessError> {
+        let language = s.language(&self.language_name)?;
+        Ok(parse_with_notation(s, language, file_name, source)?)
+    }
+}
+
+/// Parse a whole document, using the language's source notation to determine its syntax.
+pub fn parse_with_notation(
+    s: &mut Storage,
+    language: Language,
+    file_name: &str,
+    source: &str,
+) -> Result<Node, ParseError> {
+    let grammar_error = |message: String| ParseError {
+        pos: None,
+        file_name: file_name.to_owned(),
+        message,
+    };
+
+    let syntaxes = SyntaxTable::new(s, language).map_err(grammar_error)?;
+    let mut parser = Parser {
+        syntaxes: &syntaxes,
+        source,
+        pos: 0,
+        furthest: 0,
+        expected: Vec::new(),
+        fatal: None,
+        stack: Vec::new(),
+    };
+
+    let root = parser
+        .parse_construct(s, language.root_construct(s))
+        .and_then(|root| {
+            parser.skip_whitespace();
+            if parser.pos == source.len() {
+                Ok(root)
+            } else {
+                parser.fail("end of file".to_owned())
+            }
+        });
+    match root {
+        Ok(root) => Ok(root.into_node(s)),
+        Err(()) => {
+            let (pos, message) = if let Some(fatal) = parser.fatal {
+                fatal
+            } else {
+                (parser.furthest, parser.expected_message())
+            };
+            Err(ParseError {
+                pos: Some(offset_to_pos(source, pos)),
+                file_name: file_name.to_owned(),
+                message,
+            })
+        }
+    }
+}
+
+/**********
+ * Syntax *
+ **********/
+
+/// The parts of a notation that matter for parsing.
+#[derive(Debug, Clone)]
+enum Syntax {
+    /// The whitespace-separated words of a literal. `space_after` says whether the literal ended in
+    /// whitespace.
+    Literal {
+        words: Vec<String>,
+        space_after: bool,
+    },
+    /// The text of a texty node. It ends at `terminator` if there is one, and otherwise at the
+    /// first whitespace or punctuation. Leading whitespace is skipped only if `skip_space`.
+    Text {
+        terminator: Option<String>,
+        skip_space: bool,
+    },
+    /// The i'th child of a fixed node.
+    Child(usize),
+    /// The next child of a listy node.
+    Element,
+    Seq(Vec<Syntax>),
+    /// Ordered choice: the first alternative that parses wins.
+    Alt(Vec<Syntax>),
+    /// `first` followed by any number of `join`s, where `join` is the notation's `join` with the
+    /// leading `Left` removed.
+    Fold {
+        first: Box<Syntax>,
+        join: Box<Syntax>,
+    },
+    /// Only allowed at the very start of a `Fold`'s `join`. Removed before parsing.
+    Left,
+}
+
+impl Syntax {
+    fn from_notation(notation: &Notation, is_listy: bool) -> Result<Syntax, String> {
+        use ppp::Notation::*;
+
+        let convert = |notation: &Notation| Syntax::from_notation(notation, is_listy);
+        Ok(match notation {
+            Empty | Newline | EndOfLine | FocusMark => Syntax::Seq(Vec::new()),
+            Literal(string) => {
+                let words = string
+                    .split_whitespace()
+                    .map(|word| word.to_owned())
+                    .collect::<Vec<_>>();
+                if words.is_empty() {
+                    Syntax::Seq(Vec::new())
+                } else {
+                    Syntax::Literal {
+                        words,
+                        space_after: string.ends_with(char::is_whitespace),
+                    }
+                }
+            }
+            Text => Syntax::Text {
+                terminator: None,
+                skip_space: true,
+            },
+            Flat(notation) | Indent(_, _, notation) | Style(_, notation) => convert(notation)?,
+            Concat(left, right) => {
+                let mut seq = Vec::new();
+                push_flattened(&mut seq, convert(left)?);
+                push_flattened(&mut seq, convert(right)?);
+                Syntax::Seq(seq)
+            }
+            Choice(first, second) => Syntax::Alt(vec![convert(first)?, convert(second)?]),
+            Check(_, _, then, otherwise) => Syntax::Alt(vec![convert(then)?, convert(otherwise)?]),
+            // Try the longest form first, so that it isn't cut short by a shorter one.
+            Count { zero, one, many } => {
+                Syntax::Alt(vec![convert(many)?, convert(one)?, convert(zero)?])
+            }
+            Fold { first, join } => {
+                let mut join_seq = Vec::new();
+                push_flattened(&mut join_seq, convert(join)?);
+                if !matches!(join_seq.first(), Some(Syntax::Left)) {
+                    return Err("the 'join' of a 'Fold' must start with 'Left'".to_owned());
+                }
+                join_seq.remove(0);
+                Syntax::Fold {
+                    first: Box::new(convert(first)?),
+                    join: Box::new(Syntax::Seq(join_seq)),
+                }
+            }
+            Left => Syntax::Left,
+            Right => Syntax::Element,
+            Child(_) if is_listy => Syntax::Element,
+            Child(i) => Syntax::Child(*i),
+        })
+    }
+
+    /// Fill in the `terminator` and `skip_space` of each `Text`, based on its neighbors.
+    fn link_text(&mut self) -> Result<(), String> {
+        match self {
+            Syntax::Seq(seq) => {
+                for i in 0..seq.len() {
+                    let prev_literal_space = match i.checked_sub(1).map(|j| &seq[j]) {
+                        Some(Syntax::Literal { space_after, .. }) => Some(*space_after),
+                        _ => None,
+                    };
+                    let next_word = match seq.get(i + 1) {
+                        Some(Syntax::Literal { words, .. }) => Some(words[0].clone()),
+                        _ => None,
+                    };
+                    if let Syntax::Text {
+                        terminator,
+                        skip_space,
+                    } = &mut seq[i]
+                    {
+                        *terminator = next_word;
+                        *skip_space = prev_literal_space.unwrap_or(true);
+                    } else {
+                        seq[i].link_text()?;
+                    }
+                }
+            }
+            Syntax::Alt(alts) => {
+                for alt in alts {
+                    alt.link_text()?;
+                }
+            }
+            Syntax::Fold { first, join } => {
+                first.link_text()?;
+                join.link_text()?;
+            }
+            Syntax::Left => return Err("'Left' can only be used in a 'Fold'".to_owned()),
+            Syntax::Literal { .. } | Syntax::Text { .. } | Syntax::Child(_) | Syntax::Element => {}
+        }
+        Ok(())
+    }
+}
+
+fn push_flattened(seq: &mut Vec<Syntax>, syntax: Syntax) {
+    if let Syntax::Seq(inner) = syntax {
+        seq.extend(inner);
+    } else {
+        seq.push(syntax);
+    }
+}
+
+/// How a construct (or piece of syntax) can start.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct FirstSet {
+    /// The literal words it can start with.
+    words: Vec<String>,
+    /// Whether it can start with text.
+    text: bool,
+    /// Whether it can match nothing at all.
+    empty: bool,
+}
+
+impl FirstSet {
+    fn union(&mut self, other: &FirstSet) {
+        for word in &other.words {
+            if !self.words.contains(word) {
+                self.words.push(word.clone());
+            }
+        }
+        self.text |= other.text;
+        self.empty |= other.empty;
+    }
+}
+
+/// The syntax of every construct in a language, together with how each construct can start.
+struct SyntaxTable {
+    entries: Vec<SyntaxEntry>,
+    /// Characters that end a text without a terminator: the first character of every literal
+    /// that starts with punctuation.
+    stop_chars: HashSet<char>,
+}
+
+struct SyntaxEntry {
+    construct: Construct,
+    syntax: Syntax,
+    first: FirstSet,
+}
+
+impl SyntaxTable {
+    fn new(s: &Storage, language: Language) -> Result<SyntaxTable, String> {
+        let language_name = language.name(s);
+        let notation_set = language.source_notation(s).ok_or_else(|| {
+            format!("Language '{language_name}' has no source notation to parse with")
+        })?;
+
+        let mut entries = Vec::new();
+        for construct in language.constructs(s) {
+            let notation = notation_set.raw_notation(s, construct);
+            let is_listy = matches!(construct.arity(s), Arity::Listy(_));
+            let syntax = Syntax::from_notation(notation, is_listy)
+                .and_then(|mut syntax| syntax.link_text().map(|()| syntax))
+                .map_err(|msg| {
+                    format!(
+                        "Cannot parse using the source notation for '{}': {}",
+                        construct.name(s),
+                        msg
+                    )
+                })?;
+            entries.push(SyntaxEntry {
+                construct,
+                syntax,
+                first: FirstSet::default(),
+            });
+        }
+
+        let mut stop_chars = HashSet::new();
+        for entry in &entries {
+            collect_stop_chars(&entry.syntax, &mut stop_chars);
+        }
+
+        let mut table = SyntaxTable {
+            entries,
+            stop_chars,
+        };
+        table.compute_first_sets(s);
+        table.check_ambiguity(s, language)?;
+        Ok(table)
+    }
+
+    fn entry(&self, construct: Construct) -> &SyntaxEntry {
+        self.entries
+            .iter()
+            .find(|entry| entry.construct == construct)
+            .bug_msg("NotationParser: missing construct")
+    }
+
+    /// Compute the `FirstSet` of every construct. They depend on each other, so iterate until
+    /// nothing changes.
+    fn compute_first_sets(&mut self, s: &Storage) {
+        loop {
+            let mut changed = false;
+            for i in 0..self.entries.len() {
+                let entry = &self.entries[i];
+                let first = self.first_set(s, entry.construct, &entry.syntax);
+                if first != self.entries[i].first {
+                    self.entries[i].first = first;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return;
+            }
+        }
+    }
+
+    fn first_set(&self, s: &Storage, construct: Construct, syntax: &Syntax) -> FirstSet {
+        match syntax {
+            Syntax::Literal { words, .. } => FirstSet {
+                words: vec![words[0].clone()],
+                text: false,
+                empty: false,
+            },
+            Syntax::Text { .. } => FirstSet {
+                words: Vec::new(),
+                text: true,
+                empty: false,
+            },
+            Syntax::Child(i) => self.sort_first_set(s, child_sort(s, construct, *i)),
+            Syntax::Element => self.sort_first_set(s, child_sort(s, construct, 0)),
+            Syntax::Seq(seq) => {
+                let mut first = FirstSet::default();
+                for syntax in seq {
+                    let syntax_first = self.first_set(s, construct, syntax);
+                    first.union(&syntax_first);
+                    if !syntax_first.empty {
+                        first.empty = false;
+                        return first;
+                    }
+                }
+                first.empty = true;
+                first
+            }
+            Syntax::Alt(alts) => {
+                let mut first = FirstSet::default();
+                for alt in alts {
+                    first.union(&self.first_set(s, construct, alt));
+                }
+                first
+            }
+            Syntax::Fold { first, .. } => {
+                let mut first = self.first_set(s, construct, first);
+                first.empty = true;
+                first
+            }
+            Syntax::Left => FirstSet::default(),
+        }
+    }
+
+    fn sort_first_set(&self, s: &Storage, sort: Sort) -> FirstSet {
+        let mut first = FirstSet::default();
+        for construct in parseable_constructs(s, sort) {
+            first.union(&self.entry(construct).first);
+        }
+        first
+    }
+
+    /// Check that whenever a child can be one of several constructs, the first token of the
+    /// child determines which construct it is.
+    fn check_ambiguity(&self, s: &Storage, language: Language) -> Result<(), String> {
+        let ambiguous = |a: Construct, b: Construct, why: String| {
+            format!(
+                "Language '{}' is too ambiguous to parse from its source notation: \
+                 constructs '{}' and '{}' {}",
+                language.name(s),
+                a.name(s),
+                b.name(s),
+                why
+            )
+        };
+
+        for entry in &self.entries {
+            let sorts = match entry.construct.arity(s) {
+                Arity::Texty => Vec::new(),
+                Arity::Listy(sort) => vec![sort],
+                Arity::Fixed(sorts) => (0..sorts.len(s)).map(|i| sorts.get(s, i).bug()).collect(),
+            };
+            for sort in sorts {
+                let constructs = parseable_constructs(s, sort).collect::<Vec<_>>();
+                for (i, a) in constructs.iter().enumerate() {
+                    for b in &constructs[i + 1..] {
+                        let (first_a, first_b) = (&self.entry(*a).first, &self.entry(*b).first);
+                        let shared_word = first_a.words.iter().find(|w| first_b.words.contains(w));
+                        let why = if let Some(word) = shared_word {
+                            format!("can both start with '{}'", word)
+                        } else if first_a.text && first_b.text {
+                            "can both start with text".to_owned()
+                        } else if first_a.empty || first_b.empty {
+                            "can appear in the same place, and one of them can be empty".to_owned()
+                        } else {
+                            continue;
+                        };
+                        return Err(ambiguous(*a, *b, why));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The constructs in a sort that this parser can produce. It doesn't know the syntax of comments,
+/// so constructs that are `is_comment_or_ws` are left to hand-written parsers.
+fn parseable_constructs(s: &Storage, sort: Sort) -> impl Iterator<Item = Construct> + '_ {
+    sort.matching_constructs(s)
+        .filter(|construct| !construct.is_comment_or_ws(s))
+}
+
+fn collect_stop_chars(syntax: &Syntax, stop_chars: &mut HashSet<char>) {
+    match syntax {
+        Syntax::Literal { words, .. } => {
+            for word in words {
+                let ch = word.chars().next().bug();
+                if !is_word_char(ch) {
+                    stop_chars.insert(ch);
+                }
+            }
+        }
+        Syntax::Seq(syntaxes) | Syntax::Alt(syntaxes) => {
+            for syntax in syntaxes {
+                collect_stop_chars(syntax, stop_chars);
+            }
+        }
+        Syntax::Fold { first, join } => {
+            collect_stop_chars(first, stop_chars);
+            collect_stop_chars(join, stop_chars);
+        }
+        Syntax::Text { .. } | Syntax::Child(_) | Syntax::Element | Syntax::Left => (),
+    }
+}
+
+fn child_sort(s: &Storage, construct: Construct, i: usize) -> Sort {
+    match construct.arity(s) {
+        Arity::Fixed(sorts) => sorts
+            .get(s, i)
+            .bug_msg("NotationParser: child out of range"),
+        Arity::Listy(sort) => sort,
+        Arity::Texty => bug!("NotationParser: texty construct has children"),
+    }
+}
+
+/**********
+ * Parser *
+ **********/
+
+/// A node that has been parsed, but not yet added to the `Storage`.
+#[derive(Debug)]
+struct Parsed {
+    construct: Construct,
+    text: Option<String>,
+    /// (index, child). The index is only meaningful for fixed nodes.
+    children: Vec<(usize, Parsed)>,
+}
+
+impl Parsed {
+    fn into_node(mut self, s: &mut Storage) -> Node {
+        if let Some(text) = self.text {
+            return Node::with_text(s, self.construct, text).bug();
+        }
+        self.children.sort_by_key(|(i, _)| *i);
+        let children = self
+            .children
+            .into_iter()
+            .map(|(_, child)| child.into_node(s))
+            .collect::<Vec<_>>();
+        Node::with_children(s, self.construct, children).bug_msg("NotationParser: invalid children")
+    }
+}
+
+struct Parser<'a> {
+    syntaxes: &'a SyntaxTable,
+    source: &'a str,
+    /// Byte offset into `source`.
+    pos: usize,
+    /// The furthest position at which parsing failed, and what was expected there. Used for error
+    /// messages.
+    furthest: usize,
+    expected: Vec<String>,
+    /// An error that should stop parsing entirely, rather than backtracking.
+    fatal: Option<(usize, String)>,
+    /// The constructs currently being parsed, and where they started. Used to detect left
+    /// recursion.
+    stack: Vec<(Construct, usize)>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    /// Whether the rest of the source starts with the literal `word`, and doesn't continue the
+    /// word past its end.
+    fn starts_with_word(&self, word: &str) -> bool {
+        let rest = self.rest();
+        if !rest.starts_with(word) {
+            return false;
+        }
+        match (word.chars().last(), rest[word.len()..].chars().next()) {
+            (Some(last), Some(next)) => !(is_word_char(last) && is_word_char(next)),
+            _ => true,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Record that `expected` was expected at the current position, and fail.
+    fn fail<T>(&mut self, expected: String) -> Result<T, ()> {
+        if self.pos > self.furthest {
+            self.furthest = self.pos;
+            self.expected.clear();
+        }
+        if self.pos == self.furthest && !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
+        Err(())
+    }
+
+    fn expected_message(&self) -> String {
+        match self.expected.as_slice() {
+            [] => "Failed to parse".to_owned(),
+            [expected] => format!("Expected {}", expected),
+            expected => format!("Expected one of {}", expected.join(", ")),
+        }
+    }
+
+    fn parse_construct(&mut self, s: &Storage, construct: Construct) -> Result<Parsed, ()> {
+        if self.stack.contains(&(construct, self.pos)) {
+            self.fatal = Some((
+                self.pos,
+                format!(
+                    "Construct '{}' is left-recursive in the source notation, so it can't be parsed",
+                    construct.name(s)
+                ),
+            ));
+            return Err(());
+        }
+        self.stack.push((construct, self.pos));
+        let mut parsed = Parsed {
+            construct,
+            text: None,
+            children: Vec::new(),
+        };
+        let syntaxes = self.syntaxes;
+        let syntax = &syntaxes.entry(construct).syntax;
+        let result = self.parse_syntax(s, syntax, &mut parsed);
+        self.stack.pop();
+        result?;
+
+        match construct.arity(s) {
+            Arity::Texty if parsed.text.is_none() => parsed.text = Some(String::new()),
+            Arity::Fixed(sorts) if parsed.children.len() != sorts.len(s) => {
+                self.fatal = Some((
+                    self.pos,
+                    format!(
+                        "The source notation for '{}' does not contain each of its children exactly once",
+                        construct.name(s)
+                    ),
+                ));
+                return Err(());
+            }
+            _ => (),
+        }
+        Ok(parsed)
+    }
+
+    fn parse_sort(&mut self, s: &Storage, sort: Sort) -> Result<Parsed, ()> {
+        self.skip_whitespace();
+
+        // Try the construct that starts with the longest matching literal, and then the one that
+        // starts with text (in case the literal was really the start of some text).
+        let mut literal_match: Option<(Construct, usize)> = None;
+        let mut text_construct = None;
+        for construct in parseable_constructs(s, sort) {
+            let first = &self.syntaxes.entry(construct).first;
+            for word in &first.words {
+                let is_longer = literal_match
+                    .map(|(_, len)| word.len() > len)
+                    .unwrap_or(true);
+                if self.starts_with_word(word) && is_longer {
+                    literal_match = Some((construct, word.len()));
+                }
+            }
+            if first.text || first.empty {
+                text_construct = Some(construct);
+            }
+        }
+        let candidates = literal_match
+            .map(|(construct, _)| construct)
+            .into_iter()
+            .chain(text_construct)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            let names = sort
+                .matching_constructs(s)
+                .filter(|construct| !construct.is_hole(s) && !construct.is_comment_or_ws(s))
+                .map(|construct| construct.name(s))
+                .collect::<Vec<_>>();
+            return self.fail(names.join(" or "));
+        }
+
+        let start_pos = self.pos;
+        for construct in candidates {
+            if let Ok(parsed) = self.parse_construct(s, construct) {
+                return Ok(parsed);
+            }
+            if self.fatal.is_some() {
+                break;
+            }
+            self.pos = start_pos;
+        }
+        Err(())
+    }
+
+    fn parse_syntax(
+        &mut self,
+        s: &Storage,
+        syntax: &Syntax,
+        parsed: &mut Parsed,
+    ) -> Result<(), ()> {
+        match syntax {
+            Syntax::Literal { words, .. } => {
+                for word in words {
+                    self.skip_whitespace();
+                    if self.starts_with_word(word) {
+                        self.pos += word.len();
+                    } else {
+                        return self.fail(format!("'{}'", word));
+                    }
+                }
+                Ok(())
+            }
+            Syntax::Text {
+                terminator,
+                skip_space,
+            } => {
+                if *skip_space {
+                    self.skip_whitespace();
+                }
+                let text = self.parse_text(terminator.as_deref())?;
+                parsed.text = Some(text);
+                Ok(())
+            }
+            Syntax::Child(i) => {
+                let sort = child_sort(s, parsed.construct, *i);
+                let child = self.parse_sort(s, sort)?;
+                parsed.children.push((*i, child));
+                Ok(())
+            }
+            Syntax::Element => {
+                let sort = child_sort(s, parsed.construct, 0);
+                let child = self.parse_sort(s, sort)?;
+                let i = parsed.children.len();
+                parsed.children.push((i, child));
+                Ok(())
+            }
+            Syntax::Seq(seq) => {
+                for syntax in seq {
+                    self.parse_syntax(s, syntax, parsed)?;
+                }
+                Ok(())
+            }
+            Syntax::Alt(alts) => {
+                let (start_pos, num_children) = (self.pos, parsed.children.len());
+                for alt in alts {
+                    if self.parse_syntax(s, alt, parsed).is_ok() {
+                        return Ok(());
+                    }
+                    if self.fatal.is_some() {
+                        return Err(());
+                    }
+                    self.pos = start_pos;
+                    parsed.children.truncate(num_children);
+                    parsed.text = None;
+                }
+                Err(())
+            }
+            Syntax::Fold { first, join } => {
+                let (start_pos, num_children) = (self.pos, parsed.children.len());
+                if self.parse_syntax(s, first, parsed).is_err() {
+                    if self.fatal.is_some() {
+                        return Err(());
+                    }
+                    // No elements at all
+                    self.pos = start_pos;
+                    parsed.children.truncate(num_children);
+                    return Ok(());
+                }
+                loop {
+                    let (join_pos, num_children) = (self.pos, parsed.children.len());
+                    if self.parse_syntax(s, join, parsed).is_err() {
+                        if self.fatal.is_some() {
+                            return Err(());
+                        }
+                        self.pos = join_pos;
+                        parsed.children.truncate(num_children);
+                        return Ok(());
+                    }
+                }
+            }
+            Syntax::Left => bug!("NotationParser: unexpected 'Left'"),
+        }
+    }
+
+    /// Parse the text of a texty node. If there's a `terminator`, the text extends up to it, and
+    /// its backslash escapes are processed. Otherwise it extends up to the first whitespace or
+    /// punctuation, and must not be empty.
+    fn parse_text(&mut self, terminator: Option<&str>) -> Result<String, ()> {
+        let rest = self.rest();
+        let mut chars = rest.char_indices();
+        let len = if let Some(terminator) = terminator {
+            loop {
+                match chars.next() {
+                    None => return self.fail(format!("'{}'", terminator)),
+                    Some((i, _)) if rest[i..].starts_with(terminator) => break i,
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some(_) => (),
+                }
+            }
+        } else {
+            let stop_chars = &self.syntaxes.stop_chars;
+            let len = chars
+                .find(|(_, ch)| ch.is_whitespace() || stop_chars.contains(ch))
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return self.fail("text".to_owned());
+            }
+            len
+        };
+        let text = if terminator.is_some() {
+            match unescape(&rest[..len]) {
+                Ok(text) => text,
+                Err((offset, message)) => {
+                    self.fatal = Some((self.pos + offset, message));
+                    return Err(());
+                }
+            }
+        } else {
+            rest[..len].to_owned()
+        };
+        self.pos += len;
+        Ok(text)
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Process the backslash escapes in a quoted text, the same way that `JsonParser` does. On
+/// failure, returns the byte offset of the invalid escape and an error message.
+fn unescape(text: &str) -> Result<String, (usize, String)> {
+    let mut string = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, ch)) = chars.next() {
+        if ch != '\\' {
+            string.push(ch);
+            continue;
+        }
+        let Some((_, escaped)) = chars.next() else {
+            return Err((i, "Unterminated escape".to_owned()));
+        };
+        match escaped {
+            '"' | '\\' | '/' => string.push(escaped),
+            'b' => string.push('\u{8}'),
+            'f' => string.push('\u{c}'),
+            'n' => string.push('\n'),
+            'r' => string.push('\r'),
+            't' => string.push('\t'),
+            'u' => {
+                let hex = chars.by_ref().take(4).map(|(_, ch)| ch).collect::<String>();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(ch) => string.push(ch),
+                    None => return Err((i, format!("Invalid escape '\\u{hex}'"))),
+                }
+            }
+            _ => return Err((i, format!("Invalid escape '\\{escaped}'"))),
+        }
+    }
+    Ok(string)
+}
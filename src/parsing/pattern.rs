@@ -0,0 +1,507 @@
+//! Tree patterns, for structural search-and-replace. They're written like the s-expression format
+//! (see the `sexpr` module), extended with variables and wildcards:
+//!
+//! ```text
+//! (ObjectPair (Key "id") (Number $v))
+//! ```
+//!
+//! - `$name` is a variable. It captures the node in its position, or the text if it's in a text
+//!   position. If the same variable appears twice, both places must be equal.
+//! - `_` matches any node, or any text.
+//! - `..` at the end of a listy node's children matches any remaining children.
+//! - A texty node without any text, like `(Number)`, matches any text.
+//!
+//! A template is written the same way, without wildcards, and fills in the variables captured by
+//! a pattern. A variable that captured a texty node can be used in a text position, to take its
+//! text. So replacing the pattern above with the template below turns `"id": 5` into `"id": "5"`:
+//!
+//! ```text
+//! (ObjectPair (Key "id") (String $v))
+//! ```
+
+use super::sexpr::{tokenize, Token};
+use super::ParseError;
+use crate::language::{Arity, Construct, Language, Storage};
+use crate::tree::Node;
+use crate::util::{error, SynlessError};
+use partial_pretty_printer as ppp;
+use std::collections::HashMap;
+
+const PATTERN_FILE_NAME: &str = "pattern";
+const TEMPLATE_FILE_NAME: &str = "template";
+
+#[derive(Debug, Clone)]
+enum PatternNode {
+    Wildcard,
+    Var(String),
+    Node {
+        construct: Construct,
+        children: PatternChildren,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum PatternChildren {
+    Text(PatternText),
+    Nodes {
+        nodes: Vec<PatternNode>,
+        /// Whether the children ended with `..`.
+        allow_more: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum PatternText {
+    Any,
+    Exact(String),
+    Var(String),
+}
+
+/// What a pattern variable captured.
+#[derive(Debug, Clone, Copy)]
+enum Capture<'a> {
+    Node(Node),
+    Text(&'a str),
+}
+
+type Captures<'a> = HashMap<String, Capture<'a>>;
+
+/// A pattern to match against nodes in a tree. See the module docs for its syntax.
+#[derive(Debug, Clone)]
+pub struct TreePattern(PatternNode);
+
+/// A template for constructing replacements for the nodes matched by a [`TreePattern`].
+#[derive(Debug, Clone)]
+pub struct TreeTemplate(PatternNode);
+
+impl TreePattern {
+    pub fn parse(s: &Storage, language: Language, source: &str) -> Result<TreePattern, ParseError> {
+        let node = PatternParser::new(language, PATTERN_FILE_NAME, source, true)?.parse_all(s)?;
+        Ok(TreePattern(node))
+    }
+
+    pub fn matches(&self, s: &Storage, node: Node) -> bool {
+        match_node(s, &self.0, node, &mut HashMap::new())
+    }
+
+    /// All nodes under `root` (but not `root` itself) that match, in document order. Once a node
+    /// matches, its descendants are not searched, so matches never overlap.
+    pub fn find_all(&self, s: &Storage, root: Node) -> Vec<Node> {
+        let mut matches = Vec::new();
+        self.find_all_rec(s, root, &mut matches);
+        matches
+    }
+
+    fn find_all_rec(&self, s: &Storage, node: Node, matches: &mut Vec<Node>) {
+        let mut child = node.first_child(s);
+        while let Some(node) = child {
+            if self.matches(s, node) {
+                matches.push(node);
+            } else {
+                self.find_all_rec(s, node, matches);
+            }
+            child = node.next_sibling(s);
+        }
+    }
+}
+
+impl TreeTemplate {
+    /// Parse a template. It may only use variables that `pattern` captures.
+    pub fn parse(
+        s: &Storage,
+        language: Language,
+        source: &str,
+        pattern: &TreePattern,
+    ) -> Result<TreeTemplate, ParseError> {
+        let mut parser = PatternParser::new(language, TEMPLATE_FILE_NAME, source, false)?;
+        let node = parser.parse_all(s)?;
+        let mut pattern_vars = Vec::new();
+        collect_vars(&pattern.0, &mut pattern_vars);
+        let mut template_vars = Vec::new();
+        collect_vars(&node, &mut template_vars);
+        if let Some(var) = template_vars
+            .into_iter()
+            .find(|var| !pattern_vars.contains(var))
+        {
+            return Err(ParseError {
+                pos: None,
+                file_name: TEMPLATE_FILE_NAME.to_owned(),
+                message: format!("Variable '${}' does not appear in the pattern", var),
+            });
+        }
+        Ok(TreeTemplate(node))
+    }
+
+    /// Construct the replacement for `node`, which must match `pattern`. The replacement is a new
+    /// root node, built from copies of the captured nodes.
+    pub fn instantiate(
+        &self,
+        s: &mut Storage,
+        pattern: &TreePattern,
+        node: Node,
+    ) -> Result<Node, SynlessError> {
+        // Captures borrow the text in `s`, so copy them out before building the new node.
+        let captures = {
+            let mut captures = HashMap::new();
+            if !match_node(s, &pattern.0, node, &mut captures) {
+                return Err(error!(Edit, "Node does not match the pattern"));
+            }
+            captures
+                .into_iter()
+                .map(|(var, capture)| {
+                    let capture = match capture {
+                        Capture::Node(node) => OwnedCapture::Node(node),
+                        Capture::Text(text) => OwnedCapture::Text(text.to_owned()),
+                    };
+                    (var, capture)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        build_node(s, &self.0, &captures)
+    }
+}
+
+/************
+ * Matching *
+ ************/
+
+fn match_node<'a>(
+    s: &'a Storage,
+    pattern: &PatternNode,
+    node: Node,
+    captures: &mut Captures<'a>,
+) -> bool {
+    match pattern {
+        PatternNode::Wildcard => true,
+        PatternNode::Var(var) => match captures.get(var) {
+            Some(Capture::Node(other)) => nodes_equal(s, *other, node),
+            Some(Capture::Text(_)) => false,
+            None => {
+                captures.insert(var.to_owned(), Capture::Node(node));
+                true
+            }
+        },
+        PatternNode::Node {
+            construct,
+            children,
+        } => {
+            if node.construct(s) != *construct {
+                return false;
+            }
+            match children {
+                PatternChildren::Text(text_pattern) => {
+                    let text = match node.text(s) {
+                        Some(text) => text.as_str(),
+                        None => return false,
+                    };
+                    match text_pattern {
+                        PatternText::Any => true,
+                        PatternText::Exact(expected) => text == expected,
+                        PatternText::Var(var) => match captures.get(var) {
+                            Some(Capture::Text(other)) => *other == text,
+                            Some(Capture::Node(_)) => false,
+                            None => {
+                                captures.insert(var.to_owned(), Capture::Text(text));
+                                true
+                            }
+                        },
+                    }
+                }
+                PatternChildren::Nodes { nodes, allow_more } => {
+                    let num_children = node.num_children(s).unwrap_or(0);
+                    if num_children < nodes.len() || (!allow_more && num_children > nodes.len()) {
+                        return false;
+                    }
+                    let mut child = node.first_child(s);
+                    for child_pattern in nodes {
+                        let child_node = match child {
+                            Some(child_node) => child_node,
+                            None => return false,
+                        };
+                        if !match_node(s, child_pattern, child_node, captures) {
+                            return false;
+                        }
+                        child = child_node.next_sibling(s);
+                    }
+                    true
+                }
+            }
+        }
+    }
+}
+
+/// Whether two trees have the same constructs and text.
+fn nodes_equal(s: &Storage, node_1: Node, node_2: Node) -> bool {
+    if node_1.construct(s) != node_2.construct(s) {
+        return false;
+    }
+    if node_1.text(s).map(|text| text.as_str()) != node_2.text(s).map(|text| text.as_str()) {
+        return false;
+    }
+    let (mut child_1, mut child_2) = (node_1.first_child(s), node_2.first_child(s));
+    loop {
+        match (child_1, child_2) {
+            (None, None) => return true,
+            (Some(c1), Some(c2)) => {
+                if !nodes_equal(s, c1, c2) {
+                    return false;
+                }
+                child_1 = c1.next_sibling(s);
+                child_2 = c2.next_sibling(s);
+            }
+            _ => return false,
+        }
+    }
+}
+
+/*************
+ * Templates *
+ *************/
+
+enum OwnedCapture {
+    Node(Node),
+    Text(String),
+}
+
+fn build_node(
+    s: &mut Storage,
+    template: &PatternNode,
+    captures: &HashMap<String, OwnedCapture>,
+) -> Result<Node, SynlessError> {
+    match template {
+        PatternNode::Wildcard => Err(error!(Edit, "Templates can't contain wildcards")),
+        PatternNode::Var(var) => match captures.get(var) {
+            Some(OwnedCapture::Node(node)) => Ok(node.deep_copy(s)),
+            Some(OwnedCapture::Text(_)) => Err(error!(
+                Edit,
+                "Variable '${}' captured text, so it can't be used as a node", var
+            )),
+            None => Err(error!(Edit, "Variable '${}' was not captured", var)),
+        },
+        PatternNode::Node {
+            construct,
+            children,
+        } => match children {
+            PatternChildren::Text(text) => {
+                let text = match text {
+                    PatternText::Any => String::new(),
+                    PatternText::Exact(text) => text.to_owned(),
+                    PatternText::Var(var) => match captures.get(var) {
+                        Some(OwnedCapture::Text(text)) => text.to_owned(),
+                        Some(OwnedCapture::Node(node)) => match node.text(s) {
+                            Some(text) => text.as_str().to_owned(),
+                            None => {
+                                return Err(error!(
+                                    Edit,
+                                    "Variable '${}' captured a node without text", var
+                                ))
+                            }
+                        },
+                        None => return Err(error!(Edit, "Variable '${}' was not captured", var)),
+                    },
+                };
+                Node::with_text(s, *construct, text)
+                    .ok_or_else(|| error!(Edit, "Construct '{}' is not texty", construct.name(s)))
+            }
+            PatternChildren::Nodes { nodes, .. } => {
+                let mut children = Vec::new();
+                for child_template in nodes {
+                    match build_node(s, child_template, captures) {
+                        Ok(child) => children.push(child),
+                        Err(err) => {
+                            delete_all(s, children);
+                            return Err(err);
+                        }
+                    }
+                }
+                match Node::with_children(s, *construct, children.iter().copied()) {
+                    Some(node) => Ok(node),
+                    None => {
+                        delete_all(s, children);
+                        Err(error!(
+                            Edit,
+                            "The template's children don't fit in '{}'",
+                            construct.name(s)
+                        ))
+                    }
+                }
+            }
+        },
+    }
+}
+
+fn delete_all(s: &mut Storage, nodes: Vec<Node>) {
+    for node in nodes {
+        node.delete_root(s);
+    }
+}
+
+fn collect_vars(pattern: &PatternNode, vars: &mut Vec<String>) {
+    match pattern {
+        PatternNode::Wildcard => (),
+        PatternNode::Var(var) => vars.push(var.to_owned()),
+        PatternNode::Node {
+            children: PatternChildren::Text(PatternText::Var(var)),
+            ..
+        } => vars.push(var.to_owned()),
+        PatternNode::Node {
+            children: PatternChildren::Text(_),
+            ..
+        } => (),
+        PatternNode::Node {
+            children: PatternChildren::Nodes { nodes, .. },
+            ..
+        } => {
+            for node in nodes {
+                collect_vars(node, vars);
+            }
+        }
+    }
+}
+
+/**********
+ * Parser *
+ **********/
+
+struct PatternParser<'a> {
+    tokens: Vec<(Token, ppp::Pos)>,
+    index: usize,
+    file_name: &'a str,
+    language: Language,
+    /// Whether wildcards are allowed, i.e. whether this is a pattern rather than a template.
+    is_pattern: bool,
+}
+
+impl<'a> PatternParser<'a> {
+    fn new(
+        language: Language,
+        file_name: &'a str,
+        source: &str,
+        is_pattern: bool,
+    ) -> Result<PatternParser<'a>, ParseError> {
+        Ok(PatternParser {
+            tokens: tokenize(file_name, source)?,
+            index: 0,
+            file_name,
+            language,
+            is_pattern,
+        })
+    }
+
+    fn error(&self, pos: Option<ppp::Pos>, message: String) -> ParseError {
+        ParseError {
+            pos,
+            file_name: self.file_name.to_owned(),
+            message,
+        }
+    }
+
+    fn parse_all(&mut self, s: &Storage) -> Result<PatternNode, ParseError> {
+        let node = self.parse_node(s)?;
+        if let Some((_, pos)) = self.tokens.get(self.index) {
+            return Err(self.error(Some(*pos), "Unexpected input after the pattern".to_owned()));
+        }
+        Ok(node)
+    }
+
+    fn next(&mut self) -> Result<(Token, ppp::Pos), ParseError> {
+        if let Some(token) = self.tokens.get(self.index) {
+            self.index += 1;
+            Ok(token.clone())
+        } else {
+            Err(self.error(None, "Unexpected end of input".to_owned()))
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn parse_node(&mut self, s: &Storage) -> Result<PatternNode, ParseError> {
+        match self.next()? {
+            (Token::Name(name), pos) => {
+                if name == "_" {
+                    if !self.is_pattern {
+                        return Err(
+                            self.error(Some(pos), "Templates can't contain wildcards".to_owned())
+                        );
+                    }
+                    Ok(PatternNode::Wildcard)
+                } else if let Some(var) = name.strip_prefix('$') {
+                    Ok(PatternNode::Var(var.to_owned()))
+                } else {
+                    Err(self.error(Some(pos), format!("Expected '(' but found '{}'", name)))
+                }
+            }
+            (Token::Open, _) => {
+                let construct = match self.next()? {
+                    (Token::Name(name), pos) => {
+                        self.language.construct(s, &name).ok_or_else(|| {
+                            self.error(
+                                Some(pos),
+                                format!(
+                                    "Construct '{}' does not exist in language '{}'",
+                                    name,
+                                    self.language.name(s)
+                                ),
+                            )
+                        })?
+                    }
+                    (_, pos) => {
+                        return Err(self.error(Some(pos), "Expected a construct name".to_owned()))
+                    }
+                };
+                let children = if let Arity::Texty = construct.arity(s) {
+                    PatternChildren::Text(self.parse_text()?)
+                } else {
+                    self.parse_children(s)?
+                };
+                match self.next()? {
+                    (Token::Close, _) => Ok(PatternNode::Node {
+                        construct,
+                        children,
+                    }),
+                    (_, pos) => Err(self.error(Some(pos), "Expected ')'".to_owned())),
+                }
+            }
+            (_, pos) => Err(self.error(Some(pos), "Expected '('".to_owned())),
+        }
+    }
+
+    fn parse_text(&mut self) -> Result<PatternText, ParseError> {
+        let text = match self.peek() {
+            Some(Token::Close) => return Ok(PatternText::Any),
+            Some(Token::String(text)) => Some(PatternText::Exact(text.to_owned())),
+            Some(Token::Name(name)) => match name.strip_prefix('$') {
+                Some(var) => Some(PatternText::Var(var.to_owned())),
+                None if name == "_" && self.is_pattern => Some(PatternText::Any),
+                None => None,
+            },
+            _ => None,
+        };
+        let (_, pos) = self.next()?;
+        text.ok_or_else(|| self.error(Some(pos), "Expected text or a variable".to_owned()))
+    }
+
+    fn parse_children(&mut self, s: &Storage) -> Result<PatternChildren, ParseError> {
+        let mut nodes = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Close) => {
+                    return Ok(PatternChildren::Nodes {
+                        nodes,
+                        allow_more: false,
+                    })
+                }
+                Some(Token::Name(name)) if name == ".." && self.is_pattern => {
+                    self.index += 1;
+                    return Ok(PatternChildren::Nodes {
+                        nodes,
+                        allow_more: true,
+                    });
+                }
+                _ => nodes.push(self.parse_node(s)?),
+            }
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! A generic s-expression format that can represent a document in any language. It's built only
+//! from construct names, children, and text, so it needs no language-specific parser:
+//!
+//! ```text
+//! (Root (Array (Number "1") (String "two") (Null)))
+//! ```
+//!
+//! Each node is written as its construct name followed by its children, or, if it's texty, by its
+//! text as a quoted string (with `\"`, `\\`, `\n`, and `\t` escapes).
+
+use super::{Parse, ParseError};
+use crate::language::{Arity, Construct, Language, Storage};
+use crate::tree::Node;
+use crate::util::{SynlessBug, SynlessError};
+use partial_pretty_printer as ppp;
+
+const PARSER_NAME: &str = "builtin_sexpr_parser";
+const INDENT_WIDTH: usize = 2;
+
+/// Parses s-expressions for a single language. Can be used as the parser for any language that
+/// doesn't have one of its own.
+#[derive(Debug)]
+pub struct SexprParser {
+    language_name: String,
+}
+
+impl SexprParser {
+    pub fn new(language_name: &str) -> SexprParser {
+        SexprParser {
+            language_name: language_name.to_owned(),
+        }
+    }
+}
+
+impl Parse for SexprParser {
+    fn name(&self) -> &str {
+        PARSER_NAME
+    }
+
+    fn parse(
+        &mut self,
+        s: &mut Storage,
+        file_name: &str,
+        source: &str,
+    ) -> Result<Node, SynlessError> {
+        let language = s.language(&self.language_name)?;
+        Ok(parse_sexpr(s, language, file_name, source)?)
+    }
+}
+
+/// Parse a whole document written as an s-expression. The top-level node must be the language's
+/// root construct.
+pub fn parse_sexpr(
+    s: &mut Storage,
+    language: Language,
+    file_name: &str,
+    source: &str,
+) -> Result<Node, ParseError> {
+    let mut parser = NodeParser {
+        tokens: tokenize(file_name, source)?,
+        index: 0,
+        end_pos: end_pos(source),
+        file_name,
+        language,
+    };
+    let (root_pos, root) = parser.parse_node(s)?;
+    if !root.construct(s).is_root(s) {
+        let name = root.construct(s).name(s).to_owned();
+        root.delete_root(s);
+        return Err(parser.error(
+            root_pos,
+            format!(
+                "Expected the root construct '{}', but found '{}'",
+                language.root_construct(s).name(s),
+                name
+            ),
+        ));
+    }
+    if let Some((_, pos)) = parser.tokens.get(parser.index) {
+        let pos = *pos;
+        root.delete_root(s);
+        return Err(parser.error(
+            pos,
+            "Unexpected input after the end of the document".to_owned(),
+        ));
+    }
+    Ok(root)
+}
+
+/// Print `node` and its descendants as an s-expression. Each node is printed on one line if it
+/// fits within `width`, and otherwise has its children printed on separate, indented, lines.
+pub fn print_sexpr(s: &Storage, node: Node, width: ppp::Width) -> String {
+    let mut output = String::new();
+    print_node(s, node, 0, width as usize, &mut output);
+    output
+}
+
+fn print_node(s: &Storage, node: Node, indent: usize, width: usize, output: &mut String) {
+    let mut flat = String::new();
+    print_flat(s, node, &mut flat);
+    if indent + flat.chars().count() <= width || node.first_child(s).is_none() {
+        output.push_str(&flat);
+        return;
+    }
+
+    output.push('(');
+    output.push_str(node.construct(s).name(s));
+    let mut opt_child = node.first_child(s);
+    while let Some(child) = opt_child {
+        output.push('\n');
+        output.push_str(&" ".repeat(indent + INDENT_WIDTH));
+        print_node(s, child, indent + INDENT_WIDTH, width, output);
+        opt_child = child.next_sibling(s);
+    }
+    output.push(')');
+}
+
+fn print_flat(s: &Storage, node: Node, output: &mut String) {
+    output.push('(');
+    output.push_str(node.construct(s).name(s));
+    if let Some(text) = node.text(s) {
+        output.push(' ');
+        push_quoted(text.as_str(), output);
+    }
+    let mut opt_child = node.first_child(s);
+    while let Some(child) = opt_child {
+        output.push(' ');
+        print_flat(s, child, output);
+        opt_child = child.next_sibling(s);
+    }
+    output.push(')');
+}
+
+fn push_quoted(text: &str, output: &mut String) {
+    output.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            _ => output.push(ch),
+        }
+    }
+    output.push('"');
+}
+
+/*************
+ * Tokenizer *
+ *************/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    Open,
+    Close,
+    /// A construct name, or any other bare word.
+    Name(String),
+    /// A quoted string, with its escapes already processed.
+    String(String),
+}
+
+/// Split an s-expression into tokens, each paired with the position it starts at.
+pub(crate) fn tokenize(
+    file_name: &str,
+    source: &str,
+) -> Result<Vec<(Token, ppp::Pos)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut pos = ppp::Pos { row: 0, col: 0 };
+    let mut chars = source.chars().peekable();
+
+    fn advance(pos: &mut ppp::Pos, ch: char) {
+        if ch == '\n' {
+            pos.row += 1;
+            pos.col = 0;
+        } else {
+            pos.col += 1;
+        }
+    }
+
+    while let Some(&ch) = chars.peek() {
+        let start = pos;
+        if ch.is_whitespace() {
+            chars.next();
+            advance(&mut pos, ch);
+        } else if ch == '(' || ch == ')' {
+            chars.next();
+            advance(&mut pos, ch);
+            let token = if ch == '(' { Token::Open } else { Token::Close };
+            tokens.push((token, start));
+        } else if ch == '"' {
+            chars.next();
+            advance(&mut pos, ch);
+            let mut string = String::new();
+            loop {
+                let ch = chars.next().ok_or_else(|| ParseError {
+                    pos: Some(start),
+                    file_name: file_name.to_owned(),
+                    message: "Unterminated string".to_owned(),
+                })?;
+                advance(&mut pos, ch);
+                match ch {
+                    '"' => break,
+                    '\\' => {
+                        let escape_pos = pos;
+                        let escaped = chars.next().map(|ch| {
+                            advance(&mut pos, ch);
+                            ch
+                        });
+                        match escaped {
+                            Some('"') => string.push('"'),
+                            Some('\\') => string.push('\\'),
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            _ => {
+                                return Err(ParseError {
+                                    pos: Some(escape_pos),
+                                    file_name: file_name.to_owned(),
+                                    message: "Invalid escape sequence in string".to_owned(),
+                                })
+                            }
+                        }
+                    }
+                    _ => string.push(ch),
+                }
+            }
+            tokens.push((Token::String(string), start));
+        } else {
+            let mut name = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() || ch == '(' || ch == ')' || ch == '"' {
+                    break;
+                }
+                chars.next();
+                advance(&mut pos, ch);
+                name.push(ch);
+            }
+            tokens.push((Token::Name(name), start));
+        }
+    }
+    Ok(tokens)
+}
+
+fn end_pos(source: &str) -> ppp::Pos {
+    let row = source.chars().filter(|ch| *ch == '\n').count() as ppp::Row;
+    let last_line = source.rsplit('\n').next().unwrap_or("");
+    let col = last_line.chars().count() as ppp::Col;
+    ppp::Pos { row, col }
+}
+
+/**********
+ * Parser *
+ **********/
+
+struct NodeParser<'a> {
+    tokens: Vec<(Token, ppp::Pos)>,
+    index: usize,
+    end_pos: ppp::Pos,
+    file_name: &'a str,
+    language: Language,
+}
+
+impl<'a> NodeParser<'a> {
+    fn error(&self, pos: ppp::Pos, message: String) -> ParseError {
+        ParseError {
+            pos: Some(pos),
+            file_name: self.file_name.to_owned(),
+            message,
+        }
+    }
+
+    fn next(&mut self) -> Result<(Token, ppp::Pos), ParseError> {
+        if let Some(token) = self.tokens.get(self.index) {
+            self.index += 1;
+            Ok(token.clone())
+        } else {
+            Err(self.error(self.end_pos, "Unexpected end of input".to_owned()))
+        }
+    }
+
+    fn peek_is_close(&self) -> bool {
+        matches!(self.tokens.get(self.index), Some((Token::Close, _)))
+    }
+
+    fn construct(&self, s: &Storage, name: &str, pos: ppp::Pos) -> Result<Construct, ParseError> {
+        self.language.construct(s, name).ok_or_else(|| {
+            self.error(
+                pos,
+                format!(
+                    "Construct '{}' does not exist in language '{}'",
+                    name,
+                    self.language.name(s)
+                ),
+            )
+        })
+    }
+
+    /// Parse a single node and its descendants, returning it along with its starting position.
+    fn parse_node(&mut self, s: &mut Storage) -> Result<(ppp::Pos, Node), ParseError> {
+        let start = match self.next()? {
+            (Token::Open, pos) => pos,
+            (_, pos) => return Err(self.error(pos, "Expected '('".to_owned())),
+        };
+        let construct = match self.next()? {
+            (Token::Name(name), pos) => self.construct(s, &name, pos)?,
+            (_, pos) => return Err(self.error(pos, "Expected a construct name".to_owned())),
+        };
+
+        let node = match construct.arity(s) {
+            Arity::Texty => {
+                let text = if self.peek_is_close() {
+                    String::new()
+                } else {
+                    match self.next()? {
+                        (Token::String(text), _) => text,
+                        (_, pos) => {
+                            return Err(self.error(
+                                pos,
+                                format!(
+                                    "Expected a string, because '{}' is texty",
+                                    construct.name(s)
+                                ),
+                            ))
+                        }
+                    }
+                };
+                Node::with_text(s, construct, text).ok_or_else(|| {
+                    self.error(start, "Bug in sexpr parser: arity mismatch".to_owned())
+                })?
+            }
+            Arity::Fixed(sorts) => {
+                let children = self.parse_children(s)?;
+                let num_sorts = sorts.len(s);
+                let mut error = None;
+                if children.len() != num_sorts {
+                    error = Some(self.error(
+                        start,
+                        format!(
+                            "'{}' must have exactly {} children, but has {}",
+                            construct.name(s),
+                            num_sorts,
+                            children.len()
+                        ),
+                    ));
+                } else {
+                    for (i, (pos, child)) in children.iter().enumerate() {
+                        if !sorts.get(s, i).bug().accepts(s, child.construct(s)) {
+                            error = Some(self.error(
+                                *pos,
+                                format!(
+                                    "'{}' is not allowed as child {} of '{}'",
+                                    child.construct(s).name(s),
+                                    i,
+                                    construct.name(s)
+                                ),
+                            ));
+                            break;
+                        }
+                    }
+                }
+                if let Some(error) = error {
+                    delete_all(s, children);
+                    return Err(error);
+                }
+                let child_nodes = children.into_iter().map(|(_, child)| child);
+                Node::with_children(s, construct, child_nodes).ok_or_else(|| {
+                    self.error(start, "Bug in sexpr parser: arity mismatch".to_owned())
+                })?
+            }
+            Arity::Listy(sort) => {
+                let children = self.parse_children(s)?;
+                let opt_bad_child = children
+                    .iter()
+                    .find(|(_, child)| !sort.accepts(s, child.construct(s)));
+                if let Some((pos, child)) = opt_bad_child {
+                    let error = self.error(
+                        *pos,
+                        format!(
+                            "'{}' is not allowed as a child of '{}'",
+                            child.construct(s).name(s),
+                            construct.name(s)
+                        ),
+                    );
+                    delete_all(s, children);
+                    return Err(error);
+                }
+                let child_nodes = children.into_iter().map(|(_, child)| child);
+                Node::with_children(s, construct, child_nodes).ok_or_else(|| {
+                    self.error(start, "Bug in sexpr parser: arity mismatch".to_owned())
+                })?
+            }
+        };
+
+        match self.next() {
+            Ok((Token::Close, _)) => Ok((start, node)),
+            Ok((_, pos)) => {
+                node.delete_root(s);
+                Err(self.error(pos, "Expected ')'".to_owned()))
+            }
+            Err(err) => {
+                node.delete_root(s);
+                Err(err)
+            }
+        }
+    }
+
+    /// Parse nodes until reaching a `)` (which is not consumed).
+    fn parse_children(&mut self, s: &mut Storage) -> Result<Vec<(ppp::Pos, Node)>, ParseError> {
+        let mut children = Vec::new();
+        while !self.peek_is_close() {
+            match self.parse_node(s) {
+                Ok(child) => children.push(child),
+                Err(err) => {
+                    delete_all(s, children);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(children)
+    }
+}
+
+fn delete_all(s: &mut Storage, nodes: Vec<(ppp::Pos, Node)>) {
+    for (_, node) in nodes {
+        node.delete_root(s);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::language::Storage;
 use crate::style::{
     Condition, Style, StyleLabel, ValidNotation, CURSOR_STYLE, HOLE_STYLE, OPEN_STYLE,
+    SEARCH_MATCH_STYLE, SELECTION_STYLE,
 };
-use crate::tree::{Location, Node, NodeId};
+use crate::tree::{Location, Node, NodeId, SearchQuery};
 use crate::util::{error, SynlessBug, SynlessError};
 use partial_pretty_printer as ppp;
 use std::fmt;
@@ -17,6 +18,10 @@ pub enum PrettyDocError {
 pub struct DocRef<'d> {
     storage: &'d Storage,
     cursor_loc: Option<Location>,
+    /// The first and last nodes of a selected range of siblings, if any.
+    selection: Option<(Node, Node)>,
+    /// Highlight the nodes that match this query, if any.
+    search: Option<&'d SearchQuery>,
     node: Node,
     use_source_notation: bool,
 }
@@ -30,6 +35,8 @@ impl<'d> DocRef<'d> {
         DocRef {
             storage,
             cursor_loc,
+            selection: None,
+            search: None,
             node,
             use_source_notation: false,
         }
@@ -43,10 +50,39 @@ impl<'d> DocRef<'d> {
         DocRef {
             storage,
             cursor_loc,
+            selection: None,
+            search: None,
             node,
             use_source_notation: true,
         }
     }
+
+    /// Highlight the siblings from `first` to `last` (inclusive) as selected.
+    pub fn with_selection(self, first: Node, last: Node) -> DocRef<'d> {
+        DocRef {
+            selection: Some((first, last)),
+            ..self
+        }
+    }
+
+    /// Highlight the nodes that match `search`.
+    pub fn with_search(self, search: &'d SearchQuery) -> DocRef<'d> {
+        DocRef {
+            search: Some(search),
+            ..self
+        }
+    }
+
+    fn is_selected(self) -> bool {
+        let Some((first, last)) = self.selection else {
+            return false;
+        };
+        if self.node.parent(self.storage) != first.parent(self.storage) {
+            return false;
+        }
+        let index = self.node.sibling_index(self.storage);
+        first.sibling_index(self.storage) <= index && index <= last.sibling_index(self.storage)
+    }
 }
 
 impl<'d> ppp::PrettyDoc<'d> for DocRef<'d> {
@@ -125,6 +161,7 @@ impl<'d> ppp::PrettyDoc<'d> for DocRef<'d> {
                 underlined: underlined.map(|x| (x, priority)),
                 cursor: None,
                 is_hole: false,
+                node_id: None,
             },
         })
     }
@@ -132,10 +169,21 @@ impl<'d> ppp::PrettyDoc<'d> for DocRef<'d> {
     fn node_style(self) -> Result<Style, Self::Error> {
         let style = if self.cursor_loc.and_then(|loc| loc.node(self.storage)) == Some(self.node) {
             CURSOR_STYLE
+        } else if self.is_selected() {
+            SELECTION_STYLE
+        } else if self
+            .search
+            .map(|search| search.matches(self.storage, self.node))
+            .unwrap_or(false)
+        {
+            SEARCH_MATCH_STYLE
         } else {
             Style::default()
         };
-        Ok(style)
+        Ok(Style {
+            node_id: Some(self.node.id(self.storage)),
+            ..style
+        })
     }
 
     fn num_children(self) -> Result<Option<usize>, Self::Error> {
@@ -2,16 +2,24 @@ use crate::engine::{
     BookmarkCommand, ClipboardCommand, DocDisplayLabel, DocName, Engine, Settings, TextEdCommand,
     TextNavCommand, TreeEdCommand, TreeNavCommand,
 };
-use crate::frontends::{Event, Frontend, Key};
-use crate::keymap::{KeyLookupResult, KeyProg, Keymap, Layer, LayerManager, MenuSelectionCmd};
-use crate::language::{Construct, Language};
+use crate::frontends::{
+    Event, Frontend, JumpLabels, JumpTargetKind, Key, MouseButton, MouseEvent, RecordingWindow,
+    ScreenMap,
+};
+use crate::keymap::{
+    KeyLookupResult, KeyMacro, KeyProg, Keymap, Layer, LayerManager, MacroRecorder,
+    MenuSelectionCmd,
+};
+use crate::language::{Arity, Construct, Language};
 use crate::style::Style;
-use crate::tree::{Mode, Node};
-use crate::util::{error, log, LogEntry, LogLevel, SynlessBug, SynlessError};
+use crate::tree::{Mode, Node, NodeId};
+use crate::util::{bug_assert, error, log, LogEntry, LogLevel, SynlessBug, SynlessError};
 use partial_pretty_printer::pane;
 use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // TODO: Rename Runtime -> Editor, put it in src/editor.rs?
 
@@ -20,20 +28,58 @@ const CANDIDATE_SELECTION_DOC_LABEL: &str = "selection_menu";
 const MENU_NAME_LABEL: &str = "menu_name";
 const MODE_LABEL: &str = "mode";
 const FILENAME_LABEL: &str = "filename";
+const MODIFIED_LABEL: &str = "modified";
 const SIBLING_INDEX_LABEL: &str = "sibling_index";
+const LINE_NUMBER_LABEL: &str = "line_number";
+const HOLE_COUNT_LABEL: &str = "hole_count";
 const LAST_LOG_LABEL: &str = "last_log";
+const TAB_BAR_LABEL: &str = "tab_bar";
+const TAB_BAR_LANGUAGE_NAME: &str = "tab_bar";
+/// While a menu of this name is open, what's typed into it is used as the search query.
+const SEARCH_MENU_NAME: &str = "search";
+/// While a menu of this name is open, jump labels are shown, and typing one jumps to its node.
+const JUMP_LABEL_MENU_NAME: &str = "jump_labels";
 
 const KEYHINTS_PANE_WIDTH: usize = 15;
 
 const LOG_LEVEL_TO_DISPLAY: LogLevel = LogLevel::Info;
 
+const RECOVERY_SOURCE_EXTENSION: &str = "recovery";
+const RECOVERY_SEXPR_EXTENSION: &str = "sexpr.recovery";
+
 pub struct Runtime<F: Frontend<Style = Style>> {
     engine: Engine,
     default_pane_notation: pane::PaneNotation<DocDisplayLabel, Style>,
     menu_pane_notation: pane::PaneNotation<DocDisplayLabel, Style>,
+    /// How the screen is split, if it is.
+    split_layout: Option<SplitLayout>,
     frontend: F,
+    /// Which node printed each character on the screen, as of the last display.
+    screen_map: ScreenMap,
+    /// Labels to show while the jump label menu is open.
+    jump_labels: Option<JumpLabels>,
     layers: LayerManager,
     last_log: Option<LogEntry>,
+    last_autosave: Instant,
+    /// The files that have recovery files, written since the editor started.
+    autosaved_files: HashSet<PathBuf>,
+    macros: MacroRecorder,
+    /// The macros being replayed (innermost last), each with its remaining keys. The keys are
+    /// handled before any real input. A macro stays on the stack until the program run by its last
+    /// key finishes, so that it can't replay itself, and so that the whole replay can be made one
+    /// undo group.
+    replay_stack: Vec<(char, VecDeque<Key>)>,
+    /// Whether the last `KeyProg` that was run failed, which stops any replay or repeated edit.
+    prog_failed: bool,
+    /// The action being run, and the engine's edit count when it started. An action is the
+    /// `KeyProg` for a key pressed outside of any menu, followed by the `KeyProg`s that closed the
+    /// menus it opened (which have the selected candidates curried into them).
+    action: Option<(Vec<KeyProg>, usize)>,
+    /// The last action that made an edit, for `repeat_last_edit`.
+    last_edit: Option<Vec<KeyProg>>,
+    /// The `KeyProg`s from `last_edit` that are left to re-run. They're handed out before any
+    /// input is handled, and menus aren't opened while they're being run.
+    repeat_progs: VecDeque<KeyProg>,
 }
 
 impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
@@ -45,11 +91,22 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
 
         Runtime {
             engine,
-            default_pane_notation: make_pane_notation(false),
-            menu_pane_notation: make_pane_notation(true),
+            default_pane_notation: make_pane_notation(false, None),
+            menu_pane_notation: make_pane_notation(true, None),
+            split_layout: None,
             frontend,
+            screen_map: ScreenMap::new(),
+            jump_labels: None,
             layers: LayerManager::new(),
             last_log: None,
+            last_autosave: Instant::now(),
+            autosaved_files: HashSet::new(),
+            macros: MacroRecorder::new(),
+            replay_stack: Vec::new(),
+            prog_failed: false,
+            action: None,
+            last_edit: None,
+            repeat_progs: VecDeque::new(),
         }
     }
 
@@ -74,6 +131,10 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         menu_name: String,
         description: String,
     ) -> Result<(), SynlessError> {
+        if !self.repeat_progs.is_empty() {
+            // The selection to make from it is the next repeated prog
+            return Ok(());
+        }
         let doc_name = self.engine.visible_doc_name();
         self.layers
             .open_menu(doc_name, menu_name, description, None)
@@ -85,6 +146,10 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         description: String,
         keymap: Keymap,
     ) -> Result<(), SynlessError> {
+        if !self.repeat_progs.is_empty() {
+            // The selection to make from it is the next repeated prog
+            return Ok(());
+        }
         let doc_name = self.engine.visible_doc_name();
         self.layers
             .open_menu(doc_name, menu_name, description, Some(keymap))
@@ -103,7 +168,10 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
     }
 
     pub fn menu_selection_backspace(&mut self) -> Result<(), SynlessError> {
-        self.layers.edit_menu_selection(MenuSelectionCmd::Backspace)
+        self.layers
+            .edit_menu_selection(MenuSelectionCmd::Backspace)?;
+        self.update_incremental_search();
+        Ok(())
     }
 
     /****************
@@ -112,7 +180,32 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
 
     pub fn prepare_to_abort(&mut self) {
         log!(Error, "Synless is aborting!");
-        // TODO try to save docs
+        if let Err(err) = self.save_recovery_files() {
+            log!(Error, "{err}");
+        }
+    }
+
+    /// Called when the `KeyProg` last handed out by `block_on_key` fails. Any macro replay or
+    /// repeated edit in progress is stopped.
+    pub fn report_failed_prog(&mut self) {
+        self.prog_failed = true;
+    }
+
+    /// Called when the user deliberately quits, possibly discarding unsaved changes.
+    pub fn prepare_to_quit(&mut self) {
+        log!(Info, "Synless is quitting");
+        let paths = self
+            .engine
+            .file_docs()
+            .into_iter()
+            .filter_map(|doc_name| match doc_name {
+                DocName::File(path) => Some(path.to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for path in paths {
+            self.delete_recovery_files(&path);
+        }
     }
 
     pub fn block_on_key(&mut self) -> Result<KeyProg, SynlessError> {
@@ -120,6 +213,18 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
 
         let ctrl_c = Key::from_str("C-c").bug();
 
+        if self.prog_failed {
+            // Stop repeating at the first error. (`next_event` stops any replay.)
+            self.repeat_progs.clear();
+        }
+        if let Some(prog) = self.repeat_progs.pop_front() {
+            return Ok(prog);
+        }
+        if !self.layers.has_open_menu() {
+            self.end_action();
+            self.macros.start_action();
+        }
+
         loop {
             match self.next_event()? {
                 // TODO: Remove Ctrl-c. It's only for testing.
@@ -128,13 +233,27 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
                 }
                 Event::Key(key) => {
                     if let Some(prog) = self.handle_key(key)? {
-                        return Ok(prog);
+                        return Ok(self.start_prog(prog));
                     }
                     // wait for another key press
                 }
                 Event::Resize => self.display()?,
-                Event::Mouse(_) => (),
-                Event::Paste(_) => (), // TODO: OS paste support
+                Event::Mouse(event) => {
+                    // Like a failed keybinding, a failed click shouldn't abort the runtime.
+                    match self.handle_click(event) {
+                        Ok(Some(prog)) => return Ok(self.start_prog(prog)),
+                        Ok(None) => (),
+                        Err(err) => self.log_error(err.to_string()),
+                    }
+                    self.display()?;
+                }
+                Event::Paste(text) => {
+                    // Like a failed keybinding, a failed paste shouldn't abort the runtime.
+                    if let Err(err) = self.paste_text(&text) {
+                        self.log_error(err.to_string());
+                    }
+                    self.display()?;
+                }
             }
         }
     }
@@ -186,7 +305,16 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
      ***********/
 
     pub fn display(&mut self) -> Result<(), SynlessError> {
+        let replay_keys_left = self.replay_stack.iter().any(|(_, keys)| !keys.is_empty());
+        if replay_keys_left || !self.repeat_progs.is_empty() {
+            // Don't bother showing the intermediate states of a macro replay or repeated edit
+            return Ok(());
+        }
         self.update_auxilliary_docs();
+        if self.split_layout.is_some() && !self.engine.is_split() {
+            // The doc in the other half of the screen was closed
+            self.set_split_layout(None);
+        }
 
         self.frontend
             .start_frame()
@@ -198,13 +326,30 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         } else {
             &self.default_pane_notation
         };
-        pane::display_pane(&mut self.frontend, note, &Style::default(), &get_content)?;
+        let mut window = RecordingWindow::new(&mut self.frontend, &mut self.screen_map);
+        pane::display_pane(&mut window, note, &Style::default(), &get_content)?;
+
+        if self.layers.menu_name() != Some(JUMP_LABEL_MENU_NAME) {
+            self.jump_labels = None;
+        }
+        if let Some(labels) = &self.jump_labels {
+            let typed = self.layers.menu_input().unwrap_or("");
+            labels
+                .display(&mut self.frontend, &self.screen_map, typed)
+                .map_err(|err| error!(Frontend, "{}", err))?;
+        }
 
         self.frontend
             .end_frame()
             .map_err(|err| error!(Frontend, "{}", err))
     }
 
+    fn set_split_layout(&mut self, split_layout: Option<SplitLayout>) {
+        self.split_layout = split_layout;
+        self.default_pane_notation = make_pane_notation(false, split_layout);
+        self.menu_pane_notation = make_pane_notation(true, split_layout);
+    }
+
     fn update_auxilliary_docs(&mut self) {
         for (name, node) in [
             self.make_keyhint_doc(),
@@ -212,8 +357,12 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
             self.make_menu_name_doc(),
             self.make_mode_doc(),
             self.make_filename_doc(),
+            self.make_modified_doc(),
             self.make_sibling_index_doc(),
+            self.make_line_number_doc(),
+            self.make_hole_count_doc(),
             self.make_last_log_doc(),
+            self.make_tab_bar_doc(),
         ] {
             let _ = self.engine.delete_doc(&name);
             if let Some(node) = node {
@@ -274,6 +423,25 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         (DocName::Auxilliary(FILENAME_LABEL.to_owned()), opt_node)
     }
 
+    fn make_modified_doc(&mut self) -> (DocName, Option<Node>) {
+        use crate::style::Base16Color;
+
+        let is_modified = self
+            .engine
+            .visible_doc()
+            .map(|doc| doc.is_modified())
+            .unwrap_or(false);
+        let opt_node = if is_modified {
+            Some(
+                self.engine
+                    .make_string_doc("[modified]".to_owned(), Some(Base16Color::Base08)),
+            )
+        } else {
+            None
+        };
+        (DocName::Auxilliary(MODIFIED_LABEL.to_owned()), opt_node)
+    }
+
     fn make_sibling_index_doc(&mut self) -> (DocName, Option<Node>) {
         let opt_label = self.engine.visible_doc().map(|doc| {
             let cursor = doc.cursor();
@@ -288,12 +456,84 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         )
     }
 
+    fn make_line_number_doc(&mut self) -> (DocName, Option<Node>) {
+        let opt_label = self
+            .engine
+            .line_number_at_cursor()
+            .map(|line| format!("line {}", line));
+        let opt_node = opt_label.map(|label| self.engine.make_string_doc(label, None));
+        (DocName::Auxilliary(LINE_NUMBER_LABEL.to_owned()), opt_node)
+    }
+
+    fn make_hole_count_doc(&mut self) -> (DocName, Option<Node>) {
+        let opt_label = self.engine.num_holes().map(|count| match count {
+            1 => "1 hole".to_owned(),
+            _ => format!("{} holes", count),
+        });
+        let opt_node = opt_label.map(|label| self.engine.make_string_doc(label, None));
+        (DocName::Auxilliary(HOLE_COUNT_LABEL.to_owned()), opt_node)
+    }
+
     fn make_last_log_doc(&mut self) -> (DocName, Option<Node>) {
         let opt_message = self.last_log.as_ref().map(|entry| entry.to_string());
         let opt_node = opt_message.map(|msg| self.engine.make_string_doc(msg, None));
         (DocName::Auxilliary(LAST_LOG_LABEL.to_owned()), opt_node)
     }
 
+    fn make_tab_bar_doc(&mut self) -> (DocName, Option<Node>) {
+        let doc_name = DocName::Auxilliary(TAB_BAR_LABEL.to_owned());
+        let visible_doc_name = self.engine.visible_doc_name();
+        let mut tabs = self
+            .engine
+            .file_docs()
+            .into_iter()
+            .filter_map(|doc_name| {
+                let DocName::File(path) = doc_name else {
+                    return None;
+                };
+                let os_str = path.file_name().unwrap_or_else(|| path.as_os_str());
+                let is_visible = Some(doc_name) == visible_doc_name;
+                let is_modified = self
+                    .engine
+                    .get_doc(doc_name)
+                    .map(|doc| doc.is_modified())
+                    .unwrap_or(false);
+                Some((
+                    os_str.to_string_lossy().into_owned(),
+                    is_visible,
+                    is_modified,
+                ))
+            })
+            .collect::<Vec<_>>();
+        if tabs.is_empty() {
+            return (doc_name, None);
+        }
+        // Sort by name, so that the tabs don't jump around when switching docs
+        tabs.sort_by(|(name_1, _, _), (name_2, _, _)| name_1.cmp(name_2));
+
+        let s = self.engine.raw_storage_mut();
+        let lang = s
+            .language(TAB_BAR_LANGUAGE_NAME)
+            .bug_msg("Missing tab bar lang");
+        let c_root = lang.root_construct(s);
+        let c_visible = lang.construct(s, "Visible").bug();
+        let c_modified = lang.construct(s, "Modified").bug();
+        let c_tab = lang.construct(s, "Tab").bug();
+
+        let root = Node::new(s, c_root);
+        for (label, is_visible, is_modified) in tabs {
+            let mut node = Node::with_text(s, c_tab, label).bug();
+            if is_modified {
+                node = Node::with_children(s, c_modified, [node]).bug();
+            }
+            if is_visible {
+                node = Node::with_children(s, c_visible, [node]).bug();
+            }
+            bug_assert!(root.insert_last_child(s, node));
+        }
+        (doc_name, Some(root))
+    }
+
     /******************
      * Doc Management *
      ******************/
@@ -327,13 +567,14 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         path_to_string(&cwd)
     }
 
+    /// Open the file at `path`, and make it the visible doc. The doc is named by the file's
+    /// canonical path, so that the same file can't be opened twice under different names.
     pub fn open_doc(&mut self, path: &str) -> Result<(), SynlessError> {
         use std::fs::read_to_string;
-        use std::path::PathBuf;
 
         let source = read_to_string(path)
             .map_err(|err| error!(FileSystem, "Failed to read file at '{path}' ({err})"))?;
-        let path_buf = PathBuf::from(path);
+        let path_buf = canonicalize_new_file_path(path)?;
         let ext = path_buf
             .extension()
             .ok_or_else(|| {
@@ -355,11 +596,335 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
             .ok_or_else(|| error!(Doc, "No language registered for extension '{ext}'"))?
             .to_owned();
         let doc_name = DocName::File(path_buf);
-        self.engine
-            .load_doc_from_source(doc_name.clone(), &language_name, &source)?;
+        let parse_errors = self.engine.load_doc_from_source_with_recovery(
+            doc_name.clone(),
+            &language_name,
+            &source,
+        )?;
+        for parse_error in parse_errors {
+            log!(Warn, "{parse_error}");
+        }
         self.engine.set_visible_doc(&doc_name)
     }
 
+    /// Save the visible doc to the file it was opened from.
+    pub fn save_doc(&mut self) -> Result<(), SynlessError> {
+        let doc_name = self
+            .engine
+            .visible_doc_name()
+            .ok_or_else(|| error!(Doc, "There is no visible doc to save"))?
+            .to_owned();
+        let DocName::File(path) = &doc_name else {
+            return Err(error!(
+                Doc,
+                "Can't save '{doc_name}' because it isn't a file"
+            ));
+        };
+        let source = self.engine.print_source_and_record_lines(&doc_name)?;
+        write_file_atomically(path, &source)?;
+        self.engine.mark_saved(&doc_name)?;
+        self.delete_recovery_files(path);
+        self.log_info(format!("Saved {}", path.to_string_lossy()));
+        Ok(())
+    }
+
+    /// Save the visible doc to the file at `path`, and from then on treat it as being that file.
+    pub fn save_doc_as(&mut self, path: &str) -> Result<(), SynlessError> {
+        let old_doc_name = self
+            .engine
+            .visible_doc_name()
+            .ok_or_else(|| error!(Doc, "There is no visible doc to save"))?
+            .to_owned();
+        let new_path = canonicalize_new_file_path(path)?;
+        let new_doc_name = DocName::File(new_path.clone());
+        if new_doc_name != old_doc_name && self.engine.get_doc(&new_doc_name).is_some() {
+            return Err(error!(Doc, "Document '{new_doc_name}' is already open"));
+        }
+        let source = self.engine.print_source_and_record_lines(&old_doc_name)?;
+        write_file_atomically(&new_path, &source)?;
+        if new_doc_name != old_doc_name {
+            self.engine
+                .rename_doc(&old_doc_name, new_doc_name.clone())?;
+        }
+        self.engine.mark_saved(&new_doc_name)?;
+        if let DocName::File(old_path) = &old_doc_name {
+            self.delete_recovery_files(old_path);
+        }
+        self.delete_recovery_files(&new_path);
+        self.log_info(format!("Saved {}", new_path.to_string_lossy()));
+        Ok(())
+    }
+
+    /// Close the visible doc, discarding any unsaved changes.
+    pub fn close_doc(&mut self) -> Result<(), SynlessError> {
+        let doc_name = self
+            .engine
+            .visible_doc_name()
+            .ok_or_else(|| error!(Doc, "There is no visible doc to close"))?
+            .to_owned();
+        self.engine.delete_doc(&doc_name)?;
+        if let DocName::File(path) = &doc_name {
+            self.delete_recovery_files(path);
+        }
+        Ok(())
+    }
+
+    /// Make the already-open file at `path` be the visible doc.
+    pub fn switch_to_doc(&mut self, path: &str) -> Result<(), SynlessError> {
+        self.engine
+            .set_visible_doc(&DocName::File(PathBuf::from(path)))
+    }
+
+    /// Switch back to the file that was visible before the current one.
+    pub fn switch_to_previous_doc(&mut self) -> Result<(), SynlessError> {
+        let opt_previous = self
+            .engine
+            .file_docs()
+            .into_iter()
+            .find(|doc_name| Some(*doc_name) != self.engine.visible_doc_name())
+            .cloned();
+        let previous = opt_previous.ok_or_else(|| error!(Doc, "There is no other open file"))?;
+        self.engine.set_visible_doc(&previous)
+    }
+
+    /// The paths of all open files, from most to least recently visible.
+    pub fn list_docs(&self) -> Vec<rhai::Dynamic> {
+        self.engine
+            .file_docs()
+            .into_iter()
+            .map(|doc_name| doc_name.to_string().into())
+            .collect()
+    }
+
+    /// Split the screen into a top and bottom half, both showing the visible doc.
+    pub fn split_horizontal(&mut self) -> Result<(), SynlessError> {
+        self.split(SplitDirection::Horizontal)
+    }
+
+    /// Split the screen into a left and right half, both showing the visible doc.
+    pub fn split_vertical(&mut self) -> Result<(), SynlessError> {
+        self.split(SplitDirection::Vertical)
+    }
+
+    fn split(&mut self, direction: SplitDirection) -> Result<(), SynlessError> {
+        self.engine.split()?;
+        self.set_split_layout(Some(SplitLayout {
+            direction,
+            focus_first: true,
+        }));
+        Ok(())
+    }
+
+    /// Stop splitting the screen, keeping only the half that has focus.
+    pub fn unsplit(&mut self) {
+        self.engine.unsplit();
+        self.set_split_layout(None);
+    }
+
+    /// Move focus to the other half of a split screen.
+    pub fn switch_split_focus(&mut self) -> Result<(), SynlessError> {
+        self.engine.switch_split_focus()?;
+        let mut layout = self.split_layout.bug_msg("Missing split layout");
+        layout.focus_first = !layout.focus_first;
+        self.set_split_layout(Some(layout));
+        Ok(())
+    }
+
+    /// Whether the visible doc has unsaved changes.
+    pub fn is_modified(&self) -> bool {
+        self.engine
+            .visible_doc()
+            .map(|doc| doc.is_modified())
+            .unwrap_or(false)
+    }
+
+    /// The names of all open files that have unsaved changes.
+    pub fn modified_docs(&self) -> Vec<rhai::Dynamic> {
+        self.engine
+            .modified_docs()
+            .into_iter()
+            .map(|doc_name| doc_name.to_string().into())
+            .collect()
+    }
+
+    /******************
+     * Crash Recovery *
+     ******************/
+
+    /// Save the contents of every modified file to the recovery directory, so that they can be
+    /// restored after a crash. Uses the source notation if there is one, and s-expressions
+    /// otherwise. Recovery files written earlier for files that are no longer modified (e.g.
+    /// because their edits were undone) are deleted.
+    pub fn save_recovery_files(&mut self) -> Result<(), SynlessError> {
+        use std::fs::{create_dir_all, write};
+
+        let recovery_dir = self.engine.settings().recovery_dir.clone();
+        create_dir_all(&recovery_dir).map_err(|err| {
+            error!(
+                FileSystem,
+                "Failed to create recovery directory '{}' ({err})",
+                recovery_dir.to_string_lossy()
+            )
+        })?;
+
+        let doc_names = self
+            .engine
+            .modified_docs()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let unmodified_paths = self
+            .autosaved_files
+            .iter()
+            .filter(|path| !doc_names.contains(&DocName::File(path.to_path_buf())))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in unmodified_paths {
+            self.delete_recovery_files(&path);
+        }
+        for doc_name in doc_names {
+            let DocName::File(path) = &doc_name else {
+                continue;
+            };
+            let [source_path, sexpr_path] = self.recovery_paths(path);
+            let (contents, recovery_path, other_path) = match self.engine.print_source(&doc_name) {
+                Ok(source) => (source, source_path, sexpr_path),
+                Err(_) => (self.engine.print_sexpr(&doc_name)?, sexpr_path, source_path),
+            };
+            write(&recovery_path, contents).map_err(|err| {
+                error!(
+                    FileSystem,
+                    "Failed to write recovery file '{}' ({err})",
+                    recovery_path.to_string_lossy()
+                )
+            })?;
+            let _ = std::fs::remove_file(other_path);
+            self.autosaved_files.insert(path.to_owned());
+        }
+        Ok(())
+    }
+
+    /// Whether there's a recovery file for the visible doc that is newer than the file itself.
+    pub fn has_recovery_file(&self) -> bool {
+        use std::fs::metadata;
+
+        let Some(DocName::File(path)) = self.engine.visible_doc_name() else {
+            return false;
+        };
+        let Ok(file_time) = metadata(path).and_then(|data| data.modified()) else {
+            return false;
+        };
+        self.recovery_paths(path).iter().any(|recovery_path| {
+            metadata(recovery_path)
+                .and_then(|data| data.modified())
+                .map(|recovery_time| recovery_time > file_time)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Replace the visible doc with the contents of its recovery file. The doc will be marked as
+    /// modified, and the recovery file kept until it's saved.
+    pub fn restore_recovery_file(&mut self) -> Result<(), SynlessError> {
+        use std::fs::read_to_string;
+
+        let doc_name = self
+            .engine
+            .visible_doc_name()
+            .ok_or_else(|| error!(Doc, "There is no visible doc to restore"))?
+            .to_owned();
+        let DocName::File(path) = &doc_name else {
+            return Err(error!(
+                Doc,
+                "Can't restore '{doc_name}' because it isn't a file"
+            ));
+        };
+        let language_name = self
+            .engine
+            .visible_doc()
+            .bug()
+            .cursor()
+            .root_node(self.engine.raw_storage())
+            .language(self.engine.raw_storage())
+            .name(self.engine.raw_storage())
+            .to_owned();
+        let [source_path, sexpr_path] = self.recovery_paths(path);
+        let (recovery_path, is_sexpr) = if source_path.exists() {
+            (source_path, false)
+        } else {
+            (sexpr_path, true)
+        };
+        let contents = read_to_string(&recovery_path).map_err(|err| {
+            error!(
+                FileSystem,
+                "Failed to read recovery file '{}' ({err})",
+                recovery_path.to_string_lossy()
+            )
+        })?;
+
+        // Check that the recovery file parses before throwing away the doc.
+        let recovered_name = DocName::Auxilliary(format!("recovered:{doc_name}"));
+        if is_sexpr {
+            self.engine
+                .load_doc_from_sexpr(recovered_name.clone(), &language_name, &contents)?;
+        } else {
+            self.engine
+                .load_doc_from_source(recovered_name.clone(), &language_name, &contents)?;
+        }
+        self.engine.delete_doc(&doc_name)?;
+        self.engine.rename_doc(&recovered_name, doc_name.clone())?;
+        self.engine.mark_unsaved(&doc_name)?;
+        self.engine.set_visible_doc(&doc_name)?;
+        self.log_info(format!("Restored from {}", recovery_path.to_string_lossy()));
+        Ok(())
+    }
+
+    /// Delete the recovery file for the visible doc, if any.
+    pub fn discard_recovery_file(&mut self) {
+        if let Some(DocName::File(path)) = self.engine.visible_doc_name() {
+            let path = path.to_owned();
+            self.delete_recovery_files(&path);
+        }
+    }
+
+    /// The places that the recovery file for the file at `path` could be, written in the source
+    /// notation or as an s-expression respectively. The recovery file name is the file's full path
+    /// with its `%`s and path separators percent-encoded (e.g. `/` becomes `%2F`), so that
+    /// different files never share a recovery file.
+    fn recovery_paths(&self, path: &Path) -> [PathBuf; 2] {
+        let full_path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let mut encoded = String::new();
+        for ch in full_path.to_string_lossy().chars() {
+            if ch == '%' || ch == std::path::MAIN_SEPARATOR {
+                encoded.push_str(&format!("%{:02X}", ch as u32));
+            } else {
+                encoded.push(ch);
+            }
+        }
+        let recovery_dir = &self.engine.settings().recovery_dir;
+        [
+            recovery_dir.join(format!("{encoded}.{RECOVERY_SOURCE_EXTENSION}")),
+            recovery_dir.join(format!("{encoded}.{RECOVERY_SEXPR_EXTENSION}")),
+        ]
+    }
+
+    fn delete_recovery_files(&mut self, path: &Path) {
+        self.autosaved_files.remove(path);
+        for recovery_path in self.recovery_paths(path) {
+            let _ = std::fs::remove_file(recovery_path);
+        }
+    }
+
+    /// Save recovery files, if it's been long enough since the last time.
+    fn autosave(&mut self) {
+        if self.last_autosave.elapsed() < self.engine.settings().autosave_interval {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        if let Err(err) = self.save_recovery_files() {
+            self.log_warn(format!("Autosave failed: {}", err.message));
+        }
+    }
+
     /*************
      * Languages *
      *************/
@@ -407,25 +972,360 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         self.engine.redo()
     }
 
+    pub fn jump_back(&mut self) -> Result<(), SynlessError> {
+        self.engine.jump_back()
+    }
+
+    pub fn jump_forward(&mut self) -> Result<(), SynlessError> {
+        self.engine.jump_forward()
+    }
+
     pub fn insert_node(&mut self, construct: Construct) -> Result<(), SynlessError> {
         let node = Node::new_with_auto_fill(self.engine.raw_storage_mut(), construct);
         self.engine.execute(TreeEdCommand::Insert(node))?;
         self.engine.execute(TreeNavCommand::FirstInsertLoc)
     }
 
+    /// Replace the node at the cursor with a node of a different construct, keeping as much of
+    /// its text and children as will fit.
+    pub fn retype_node(&mut self, construct: Construct) -> Result<(), SynlessError> {
+        let doc = self
+            .engine
+            .visible_doc()
+            .ok_or_else(|| error!(Doc, "There is no visible doc"))?;
+        let node = doc
+            .cursor()
+            .node(self.engine.raw_storage())
+            .ok_or_else(|| error!(Edit, "No node to retype here"))?;
+        let s = self.engine.raw_storage_mut();
+        let drops_text = !node.text(s).map(|t| t.as_str().is_empty()).unwrap_or(true)
+            && !matches!(construct.arity(s), Arity::Texty);
+        let (new_node, num_dropped) = node.retyped_copy(s, construct);
+        self.engine.execute(TreeEdCommand::Replace(new_node))?;
+        if num_dropped > 0 {
+            self.log_warn(format!("Retyping dropped {num_dropped} child node(s)"));
+        }
+        if drops_text {
+            self.log_warn("Retyping dropped the node's text".to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn wrap_node(&mut self, construct: Construct) -> Result<(), SynlessError> {
+        self.engine.execute(TreeEdCommand::Wrap(construct))
+    }
+
+    /// Jump to the node that was on this line of source when the doc was last saved.
+    pub fn goto_line(&mut self, line: rhai::INT) -> Result<(), SynlessError> {
+        let line = usize::try_from(line)
+            .ok()
+            .filter(|line| *line > 0)
+            .ok_or_else(|| error!(Edit, "There is no line {}", line))?;
+        self.engine.goto_line(line)
+    }
+
+    /// Put jump labels on the nodes of the given kind ("all", "leaves", "texty", or "holes") that
+    /// are on screen. They're shown while the "jump_labels" menu is open, and typing one into it
+    /// jumps to its node.
+    pub fn show_jump_labels(&mut self, kind: &str) -> Result<(), SynlessError> {
+        let kind = kind.parse::<JumpTargetKind>()?;
+        let doc = self
+            .engine
+            .visible_doc()
+            .ok_or_else(|| error!(Doc, "There is no visible doc"))?;
+        let s = self.engine.raw_storage();
+        let root = doc.cursor().root_node(s);
+        let labels = JumpLabels::new(s, root, kind, &self.screen_map);
+        if labels.is_empty() {
+            return Err(error!(Edit, "Nothing on screen to jump to"));
+        }
+        self.jump_labels = Some(labels);
+        Ok(())
+    }
+
+    /// Jump to the node with this label, and close the jump label menu.
+    pub fn jump_to_label(&mut self, label: &str) -> Result<(), SynlessError> {
+        let labels = self
+            .jump_labels
+            .take()
+            .ok_or_else(|| error!(Edit, "No jump labels are shown"))?;
+        if self.layers.menu_name() == Some(JUMP_LABEL_MENU_NAME) {
+            self.layers.close_menu();
+        }
+        let node = labels
+            .lookup(label)
+            .ok_or_else(|| error!(Edit, "No jump label '{}'", label))?;
+        self.engine.jump_to_node(node, None)
+    }
+
     /*************
      * Clipboard *
      *************/
 
+    /// Each non-empty register, as a map with its `name` and a one-line `preview` of its contents.
+    pub fn list_registers(&self) -> Result<Vec<rhai::Dynamic>, SynlessError> {
+        Ok(self
+            .engine
+            .list_registers()?
+            .into_iter()
+            .map(|(name, preview)| {
+                let mut map = rhai::Map::new();
+                map.insert("name".into(), name.into());
+                map.insert("preview".into(), preview.into());
+                map.into()
+            })
+            .collect())
+    }
+
+    pub fn toggle_selection(&mut self) -> Result<(), SynlessError> {
+        self.engine.toggle_selection()
+    }
+
+    /// Set the search query. See [`SearchQuery::parse`](crate::tree::SearchQuery::parse) for its
+    /// syntax.
+    pub fn search(&mut self, query: &str) -> Result<(), SynlessError> {
+        self.engine.set_search(query)
+    }
+
+    pub fn search_construct(&mut self, construct: Construct) -> Result<(), SynlessError> {
+        self.engine.set_search_construct(construct)
+    }
+
+    pub fn clear_search(&mut self) {
+        self.engine.clear_search();
+    }
+
+    pub fn search_next(&mut self) -> Result<(), SynlessError> {
+        self.engine.search_next(false)
+    }
+
+    pub fn search_prev(&mut self) -> Result<(), SynlessError> {
+        self.engine.search_next(true)
+    }
+
+    /// For each node that `replace_all` would replace, a map with its `index` and with the `old`
+    /// and `new` source text (squashed onto one line).
+    pub fn preview_replacements(
+        &mut self,
+        pattern: &str,
+        template: &str,
+    ) -> Result<Vec<rhai::Dynamic>, SynlessError> {
+        fn one_line(source: String) -> String {
+            source.split_whitespace().collect::<Vec<_>>().join(" ")
+        }
+
+        Ok(self
+            .engine
+            .preview_replacements(pattern, template)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, (old, new))| {
+                let mut map = rhai::Map::new();
+                map.insert("index".into(), (index as rhai::INT).into());
+                map.insert("old".into(), one_line(old).into());
+                map.insert("new".into(), one_line(new).into());
+                map.into()
+            })
+            .collect())
+    }
+
+    pub fn goto_pattern_match(
+        &mut self,
+        pattern: &str,
+        index: rhai::INT,
+    ) -> Result<(), SynlessError> {
+        let index = usize::try_from(index)
+            .map_err(|_| error!(Edit, "There is no match number {}", index))?;
+        self.engine.goto_pattern_match(pattern, index)
+    }
+
+    pub fn replace_all(&mut self, pattern: &str, template: &str) -> Result<(), SynlessError> {
+        let count = self.engine.replace_all(pattern, template)?;
+        self.log_info(format!("Replaced {} node(s)", count));
+        Ok(())
+    }
+
     pub fn cut(&mut self) -> Result<(), SynlessError> {
         self.engine.execute(ClipboardCommand::Copy)?;
         self.engine.execute(TreeEdCommand::Backspace)
     }
 
+    /// Write the source text of the node at the cursor (or of the selected nodes) to the OS
+    /// clipboard.
+    pub fn copy_to_os_clipboard(&mut self) -> Result<(), SynlessError> {
+        let source = self.engine.print_source_at_cursor()?;
+        self.frontend
+            .set_clipboard(&source)
+            .map_err(|err| error!(Frontend, "{}", err))
+    }
+
+    /// If the search menu is open, search for what's been typed into it so far. Partially typed
+    /// queries may be invalid (e.g. an unclosed regex group); those just clear the search.
+    fn update_incremental_search(&mut self) {
+        if self.layers.menu_name() != Some(SEARCH_MENU_NAME) {
+            return;
+        }
+        let query = self.layers.menu_input().unwrap_or("").to_owned();
+        if self.engine.set_search(&query).is_err() {
+            self.engine.clear_search();
+        }
+    }
+
+    /// If the jump label menu is open and a whole label has been typed into it, jump to that
+    /// label's node. If what's been typed can't be the start of any label, give up.
+    fn update_jump_labels(&mut self) {
+        if self.layers.menu_name() != Some(JUMP_LABEL_MENU_NAME) {
+            return;
+        }
+        let typed = self.layers.menu_input().unwrap_or("").to_owned();
+        let Some(labels) = &self.jump_labels else {
+            return;
+        };
+        if labels.lookup(&typed).is_some() || !labels.has_prefix(&typed) {
+            if let Err(err) = self.jump_to_label(&typed) {
+                self.log_error(err.to_string());
+            }
+        }
+    }
+
+    /// Paste text from the OS. In text mode it's inserted into the text at the cursor; in tree
+    /// mode it's parsed using the doc's language, and the resulting nodes are inserted at the
+    /// cursor. Either way it's a single undo group.
+    fn paste_text(&mut self, text: &str) -> Result<(), SynlessError> {
+        let _ = self.engine.end_undo_group();
+        match self.engine.mode() {
+            Mode::Text => {
+                for ch in text.chars().filter(|ch| !ch.is_control()) {
+                    self.engine.execute(TextEdCommand::Insert(ch))?;
+                }
+            }
+            Mode::Tree => self.engine.paste_source(text)?,
+        }
+        let _ = self.engine.end_undo_group();
+        Ok(())
+    }
+
+    /**********
+     * Macros *
+     **********/
+
+    /// Start recording keypresses into the macro register `name`.
+    pub fn start_macro(&mut self, name: char) -> Result<(), SynlessError> {
+        self.macros.start_recording(name)
+    }
+
+    /// Stop recording a macro. The keys of the action that triggered this are not part of the
+    /// macro.
+    pub fn stop_macro(&mut self) -> Result<(), SynlessError> {
+        let name = self.macros.stop_recording()?;
+        self.log_info(format!("Recorded macro '{}'", name));
+        Ok(())
+    }
+
+    pub fn is_recording_macro(&self) -> bool {
+        self.macros.is_recording()
+    }
+
+    /// Replay the macro in register `name`, `count` times. The whole replay is one undo group, and
+    /// it stops at the first error. If called by a macro, the replay happens before the rest of
+    /// that macro. A macro can't replay itself.
+    pub fn replay_macro(&mut self, name: char, count: rhai::INT) -> Result<(), SynlessError> {
+        if self
+            .replay_stack
+            .iter()
+            .any(|(replaying, _)| *replaying == name)
+        {
+            return Err(error!(Keymap, "Macro '{}' can't replay itself", name));
+        }
+        let key_macro = self
+            .macros
+            .get(name)
+            .ok_or_else(|| error!(Keymap, "No macro in register '{}'", name))?;
+        let mut keys = VecDeque::new();
+        for _ in 0..count {
+            keys.extend(key_macro.keys().iter().copied());
+        }
+        if !keys.is_empty() {
+            if self.replay_stack.is_empty() {
+                let _ = self.engine.end_undo_group();
+                self.engine.suppress_undo_group_boundaries(true);
+            }
+            self.replay_stack.push((name, keys));
+        }
+        Ok(())
+    }
+
+    /// The macro in register `name`, written as a string of keys (e.g. `"j j C-s"`), so that it
+    /// can be saved in a script and restored with `set_macro`.
+    pub fn get_macro(&self, name: char) -> Result<String, SynlessError> {
+        self.macros
+            .get(name)
+            .map(|key_macro| key_macro.to_string())
+            .ok_or_else(|| error!(Keymap, "No macro in register '{}'", name))
+    }
+
+    pub fn set_macro(&mut self, name: char, keys: &str) -> Result<(), SynlessError> {
+        let key_macro = keys.parse::<KeyMacro>()?;
+        self.macros.set(name, key_macro);
+        Ok(())
+    }
+
+    /// Maps with the `name` and `keys` of every recorded macro.
+    pub fn list_macros(&self) -> Vec<rhai::Dynamic> {
+        self.macros
+            .names()
+            .into_iter()
+            .map(|name| {
+                let mut map = rhai::Map::new();
+                map.insert("name".into(), name.into());
+                map.insert(
+                    "keys".into(),
+                    self.macros.get(name).bug().to_string().into(),
+                );
+                map.into()
+            })
+            .collect()
+    }
+
+    /// Re-run the last action that made an edit at the current cursor. If that action selected
+    /// from menus (like picking a node to insert), the same selections are made again without
+    /// opening the menus.
+    pub fn repeat_last_edit(&mut self) -> Result<(), SynlessError> {
+        let progs = self
+            .last_edit
+            .clone()
+            .ok_or_else(|| error!(Edit, "No edit to repeat"))?;
+        // Repeating an edit doesn't count as a new one
+        self.action = None;
+        self.repeat_progs = progs.into();
+        Ok(())
+    }
+
     /***********
      * Private *
      ***********/
 
+    /// Called just before `prog` is handed out to be run. Starts a new action, or adds to the
+    /// current one if `prog` closes one of its menus.
+    fn start_prog(&mut self, prog: KeyProg) -> KeyProg {
+        match &mut self.action {
+            None => self.action = Some((vec![prog.clone()], self.engine.edit_count())),
+            Some((progs, _)) if prog.close_menu() => progs.push(prog.clone()),
+            Some(_) => (),
+        }
+        prog
+    }
+
+    /// Called once the current action has finished (and closed all of its menus). If it made an
+    /// edit, remember it for `repeat_last_edit`.
+    fn end_action(&mut self) {
+        if let Some((progs, edit_count)) = self.action.take() {
+            if self.engine.edit_count() != edit_count {
+                self.last_edit = Some(progs);
+            }
+        }
+    }
+
     /// If the `key` is bound to a prog that needs to be executed by rhai, then returns `Some(prog)`.
     /// Otherwise (if the `key` is not bound or is bound to something that was already handled),
     /// then returns `None`.
@@ -449,10 +1349,13 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
                 Ok(Some(key_prog))
             }
             Some(KeyLookupResult::Redisplay) => {
+                self.update_incremental_search();
+                self.update_jump_labels();
                 self.display()?;
                 Ok(None)
             }
             Some(KeyLookupResult::InsertChar(ch)) => {
+                // Typing text isn't an edit that can be repeated, since no `KeyProg` is running.
                 self.engine.execute(TextEdCommand::Insert(ch))?;
                 self.display()?;
                 Ok(None)
@@ -460,9 +1363,84 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         }
     }
 
+    /// Handle a mouse click. Clicking on a menu candidate selects it and presses "enter"; clicking
+    /// on the visible doc (while there's no menu open) moves the cursor to the node clicked on.
+    /// Returns the prog to run, if any.
+    fn handle_click(&mut self, event: MouseEvent) -> Result<Option<KeyProg>, SynlessError> {
+        use std::str::FromStr;
+
+        if !matches!(event.button, MouseButton::Left) {
+            return Ok(None);
+        }
+        let Some(id) = self.screen_map.node_at(event.click_pos) else {
+            return Ok(None);
+        };
+        if let Some(index) = self.clicked_candidate_index(id) {
+            self.layers
+                .edit_menu_selection(MenuSelectionCmd::Select(index))?;
+            return self.handle_key(Key::from_str("enter").bug());
+        }
+        if self.layers.has_open_menu() {
+            return Ok(None);
+        }
+        let Some(node) = self.engine.find_visible_node(id) else {
+            return Ok(None);
+        };
+        let char_index = node.text(self.engine.raw_storage()).and_then(|text| {
+            if text.as_str().is_empty() {
+                Some(0)
+            } else {
+                self.screen_map
+                    .char_index_at(event.click_pos, text.as_str())
+            }
+        });
+        self.engine.jump_to_node(node, char_index)?;
+        Ok(None)
+    }
+
+    /// If the node with this id is part of a menu candidate, the index of that candidate.
+    fn clicked_candidate_index(&self, id: NodeId) -> Option<usize> {
+        let doc_name = DocName::Auxilliary(CANDIDATE_SELECTION_DOC_LABEL.to_owned());
+        let s = self.engine.raw_storage();
+        let root = self.engine.get_doc(&doc_name)?.cursor().root_node(s);
+        let mut node = root.find_descendant(s, id)?;
+        while node.parent(s)? != root {
+            node = node.parent(s)?;
+        }
+        // The first child is the menu's input, not a candidate.
+        node.sibling_index(s).checked_sub(1)
+    }
+
     /// Block until the next input event.
     fn next_event(&mut self) -> Result<Event, SynlessError> {
+        if std::mem::take(&mut self.prog_failed) {
+            // Stop replaying at the first error
+            for (_, keys) in &mut self.replay_stack {
+                keys.clear();
+            }
+        }
+        while let Some((_, keys)) = self.replay_stack.last_mut() {
+            if let Some(key) = keys.pop_front() {
+                return Ok(Event::Key(key));
+            }
+            self.replay_stack.pop();
+            if self.replay_stack.is_empty() {
+                // The whole replay was one undo group
+                self.engine.suppress_undo_group_boundaries(false);
+            }
+        }
+        let event = self.next_frontend_event()?;
+        if let Event::Key(key) = event {
+            self.macros.record(key);
+        }
+        Ok(event)
+    }
+
+    fn next_frontend_event(&mut self) -> Result<Event, SynlessError> {
         loop {
+            // Checked before every wait (not just when idle), so that continuous typing still
+            // gets autosaved.
+            self.autosave();
             match self.frontend.next_event(Duration::from_secs(1)) {
                 Ok(None) => (), // continue waiting
                 Ok(Some(event)) => return Ok(event),
@@ -476,7 +1454,25 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
  * Pane Notations *
  ******************/
 
-fn make_pane_notation(include_menu: bool) -> pane::PaneNotation<DocDisplayLabel, Style> {
+#[derive(Debug, Clone, Copy)]
+enum SplitDirection {
+    /// One half above the other.
+    Horizontal,
+    /// One half beside the other.
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SplitLayout {
+    direction: SplitDirection,
+    /// Whether the top/left half has focus (and thus shows the `Visible` doc).
+    focus_first: bool,
+}
+
+fn make_pane_notation(
+    include_menu: bool,
+    split_layout: Option<SplitLayout>,
+) -> pane::PaneNotation<DocDisplayLabel, Style> {
     use crate::style::{Base16Color, Priority};
     use pane::{PaneNotation, PaneSize};
 
@@ -527,16 +1523,31 @@ fn make_pane_notation(include_menu: bool) -> pane::PaneNotation<DocDisplayLabel,
     let filename_doc = PaneNotation::Doc {
         label: DocDisplayLabel::Auxilliary(FILENAME_LABEL.to_owned()),
     };
+    let modified_doc = PaneNotation::Doc {
+        label: DocDisplayLabel::Auxilliary(MODIFIED_LABEL.to_owned()),
+    };
     let sibling_index_doc = PaneNotation::Doc {
         label: DocDisplayLabel::Auxilliary(SIBLING_INDEX_LABEL.to_owned()),
     };
+    let line_number_doc = PaneNotation::Doc {
+        label: DocDisplayLabel::Auxilliary(LINE_NUMBER_LABEL.to_owned()),
+    };
+    let hole_count_doc = PaneNotation::Doc {
+        label: DocDisplayLabel::Auxilliary(HOLE_COUNT_LABEL.to_owned()),
+    };
     let status_bar = PaneNotation::Style {
         style: status_bar_style,
         notation: Box::new(PaneNotation::Horz(vec![
             (PaneSize::Dynamic, mode_doc),
             (PaneSize::Fixed(1), padding.clone()),
             (PaneSize::Dynamic, filename_doc),
+            (PaneSize::Fixed(1), padding.clone()),
+            (PaneSize::Dynamic, modified_doc),
             (PaneSize::Proportional(1), padding.clone()),
+            (PaneSize::Dynamic, hole_count_doc),
+            (PaneSize::Fixed(1), padding.clone()),
+            (PaneSize::Dynamic, line_number_doc),
+            (PaneSize::Fixed(1), padding.clone()),
             (PaneSize::Dynamic, sibling_index_doc),
             (PaneSize::Fixed(1), padding),
         ])),
@@ -544,6 +1555,38 @@ fn make_pane_notation(include_menu: bool) -> pane::PaneNotation<DocDisplayLabel,
     let log_doc = PaneNotation::Doc {
         label: DocDisplayLabel::Auxilliary(LAST_LOG_LABEL.to_owned()),
     };
+    let tab_bar_doc = PaneNotation::Doc {
+        label: DocDisplayLabel::Auxilliary(TAB_BAR_LABEL.to_owned()),
+    };
+    let tab_bar = PaneNotation::Style {
+        style: bar_style.clone(),
+        notation: Box::new(PaneNotation::Horz(vec![
+            (PaneSize::Dynamic, tab_bar_doc),
+            (PaneSize::Proportional(1), padding.clone()),
+        ])),
+    };
+
+    let main_doc = if let Some(layout) = split_layout {
+        let split_doc = PaneNotation::Doc {
+            label: DocDisplayLabel::Split,
+        };
+        let (first, second) = if layout.focus_first {
+            (main_doc, split_doc)
+        } else {
+            (split_doc, main_doc)
+        };
+        let halves = vec![
+            (PaneSize::Proportional(1), first),
+            (PaneSize::Fixed(1), divider.clone()),
+            (PaneSize::Proportional(1), second),
+        ];
+        match layout.direction {
+            SplitDirection::Horizontal => PaneNotation::Vert(halves),
+            SplitDirection::Vertical => PaneNotation::Horz(halves),
+        }
+    } else {
+        main_doc
+    };
 
     let mut main_doc_and_menu = vec![(PaneSize::Proportional(1), main_doc)];
     if include_menu {
@@ -552,6 +1595,7 @@ fn make_pane_notation(include_menu: bool) -> pane::PaneNotation<DocDisplayLabel,
     }
 
     PaneNotation::Vert(vec![
+        (PaneSize::Fixed(1), tab_bar),
         (
             PaneSize::Proportional(1),
             PaneNotation::Horz(vec![
@@ -634,6 +1678,59 @@ fn canonicalize_path(path: &str) -> Result<rhai::Dynamic, SynlessError> {
         .into())
 }
 
+/// The canonical path of a file that may not exist yet: its directory is canonicalized, and its
+/// file name is kept as is. This way it matches the name the file would be opened under.
+fn canonicalize_new_file_path(path: &str) -> Result<PathBuf, SynlessError> {
+    let path_buf = PathBuf::from(path);
+    let file_name = path_buf
+        .file_name()
+        .ok_or_else(|| error!(FileSystem, "Path ends in `..`: {path}"))?;
+    let dir = match path_buf.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = dir
+        .canonicalize()
+        .map_err(|err| error!(FileSystem, "Invalid directory for '{path}' ({err})"))?;
+    Ok(dir.join(file_name))
+}
+
+/// Write exactly `contents` to the file at `path`. The file is replaced atomically: the contents
+/// are first written to a temporary file in the same directory, which is then renamed over the
+/// original. If the file already exists, its permissions are kept.
+fn write_file_atomically(path: &Path, contents: &str) -> Result<(), SynlessError> {
+    use std::ffi::OsString;
+    use std::fs::{metadata, remove_file, rename, File};
+    use std::io::Write;
+
+    let path_str = path.to_string_lossy();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| error!(FileSystem, "Path ends in `..`: {path_str}"))?;
+    let mut temp_file_name = OsString::from(".");
+    temp_file_name.push(file_name);
+    temp_file_name.push(".synless-tmp");
+    let temp_path = path.with_file_name(temp_file_name);
+    let permissions = metadata(path).ok().map(|data| data.permissions());
+
+    let write_temp_file = || -> std::io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()
+    };
+    if let Err(err) = write_temp_file().and_then(|()| rename(&temp_path, path)) {
+        let _ = remove_file(&temp_path);
+        return Err(error!(
+            FileSystem,
+            "Failed to write file at '{path_str}' ({err})"
+        ));
+    }
+    Ok(())
+}
+
 macro_rules! register {
     ($module:expr, $runtime:ident . $method:ident($( $param:ident : $type:ty ),*)) => {
         register!($module, $runtime . $method($( $param : $type ),*) as $method)
@@ -706,7 +1803,9 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
     pub fn register_internal_methods(rt: Rc<RefCell<Runtime<F>>>, module: &mut rhai::Module) {
         // Control Flow
         register!(module, rt.prepare_to_abort());
+        register!(module, rt.prepare_to_quit());
         register!(module, rt.block_on_key()?);
+        register!(module, rt.report_failed_prog());
 
         // Display
         register!(module, rt.display()?);
@@ -737,6 +1836,24 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         // Doc management
         register!(module, rt.current_dir()?);
         register!(module, rt.open_doc(path: &str)?);
+        register!(module, rt.save_doc()?);
+        register!(module, rt.save_doc_as(path: &str)?);
+        register!(module, rt.close_doc()?);
+        register!(module, rt.switch_to_doc(path: &str)?);
+        register!(module, rt.switch_to_previous_doc()?);
+        register!(module, rt.list_docs());
+        register!(module, rt.split_horizontal()?);
+        register!(module, rt.split_vertical()?);
+        register!(module, rt.unsplit());
+        register!(module, rt.switch_split_focus()?);
+        register!(module, rt.is_modified());
+        register!(module, rt.modified_docs());
+
+        // Crash recovery
+        register!(module, rt.save_recovery_files()?);
+        register!(module, rt.has_recovery_file());
+        register!(module, rt.restore_recovery_file()?);
+        register!(module, rt.discard_recovery_file());
 
         // Languages
         register!(module, rt.load_language(path: &str)?);
@@ -764,6 +1881,8 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         register!(module, rt, TreeNavCommand::NextLeaf as tree_nav_next_leaf);
         register!(module, rt, TreeNavCommand::PrevText as tree_nav_prev_text);
         register!(module, rt, TreeNavCommand::NextText as tree_nav_next_text);
+        register!(module, rt, TreeNavCommand::PrevHole as tree_nav_prev_hole);
+        register!(module, rt, TreeNavCommand::NextHole as tree_nav_next_hole);
         register!(module, rt, TreeNavCommand::LastChild as tree_nav_last_child);
         register!(module, rt, TreeNavCommand::Parent as tree_nav_parent);
         register!(module, rt, TreeNavCommand::EnterText as tree_nav_enter_text);
@@ -771,7 +1890,15 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         // Editing: Tree Ed
         register!(module, rt, TreeEdCommand::Backspace as tree_ed_backspace);
         register!(module, rt, TreeEdCommand::Delete as tree_ed_delete);
+        register!(module, rt, TreeEdCommand::Raise as tree_ed_raise);
+        register!(module, rt, TreeEdCommand::Unwrap as tree_ed_unwrap);
+        register!(module, rt, TreeEdCommand::MoveLeft as tree_ed_move_left);
+        register!(module, rt, TreeEdCommand::MoveRight as tree_ed_move_right);
+        register!(module, rt, TreeEdCommand::MoveFirst as tree_ed_move_first);
+        register!(module, rt, TreeEdCommand::MoveLast as tree_ed_move_last);
         register!(module, rt.insert_node(construct: Construct)?);
+        register!(module, rt.wrap_node(construct: Construct)?);
+        register!(module, rt.retype_node(construct: Construct)?);
 
         // Editing: Text Nav
         register!(module, rt, TextNavCommand::Left as text_nav_left);
@@ -788,18 +1915,48 @@ impl<F: Frontend<Style = Style> + 'static> Runtime<F> {
         // Editing: Bookmark
         register!(module, rt, BookmarkCommand::Save(ch: char) as save_bookmark);
         register!(module, rt, BookmarkCommand::Goto(ch: char) as goto_bookmark);
+        register!(module, rt.jump_back()?);
+        register!(module, rt.jump_forward()?);
 
         // Clipboard
         register!(module, rt.cut()?);
+        register!(module, rt.toggle_selection()?);
+        register!(module, rt.copy_to_os_clipboard()?);
+
+        // Search
+        register!(module, rt.search(query: &str)?);
+        register!(module, rt.search_construct(construct: Construct)?);
+        register!(module, rt.clear_search());
+        register!(module, rt.search_next()?);
+        register!(module, rt.search_prev()?);
+        register!(module, rt.preview_replacements(pattern: &str, template: &str)?);
+        register!(module, rt.goto_pattern_match(pattern: &str, index: rhai::INT)?);
+        register!(module, rt.replace_all(pattern: &str, template: &str)?);
         register!(module, rt, ClipboardCommand::Copy as copy);
         register!(module, rt, ClipboardCommand::Paste as paste);
         register!(module, rt, ClipboardCommand::PasteSwap as paste_swap);
         register!(module, rt, ClipboardCommand::Dup as dup_clipboard);
         register!(module, rt, ClipboardCommand::Pop as pop_clipboard);
+        register!(module, rt, ClipboardCommand::CopyToRegister(ch: char) as copy_to_register);
+        register!(module, rt, ClipboardCommand::PasteFromRegister(ch: char) as paste_from_register);
+        register!(module, rt.list_registers()?);
+
+        // Macros
+        register!(module, rt.repeat_last_edit()?);
+        register!(module, rt.start_macro(name: char)?);
+        register!(module, rt.stop_macro()?);
+        register!(module, rt.is_recording_macro());
+        register!(module, rt.replay_macro(name: char, count: rhai::INT)?);
+        register!(module, rt.get_macro(name: char)?);
+        register!(module, rt.set_macro(name: char, keys: &str)?);
+        register!(module, rt.list_macros());
 
         // Editing: Meta
         register!(module, rt.undo()?);
         register!(module, rt.redo()?);
+        register!(module, rt.goto_line(line: rhai::INT)?);
+        register!(module, rt.show_jump_labels(kind: &str)?);
+        register!(module, rt.jump_to_label(label: &str)?);
 
         // Logging
         register!(module, rt.log_trace(msg: String));
@@ -1,3 +1,4 @@
+use crate::tree::NodeId;
 use crate::util::SynlessBug;
 use partial_pretty_printer as ppp;
 use serde::{Deserialize, Serialize};
@@ -22,6 +23,24 @@ pub const CURSOR_STYLE: Style = Style {
     ..Style::const_default()
 };
 
+pub const SELECTION_STYLE: Style = Style {
+    bg_color: Some((Base16Color::Base02, Priority::High)),
+    ..Style::const_default()
+};
+
+pub const SEARCH_MATCH_STYLE: Style = Style {
+    fg_color: Some((Base16Color::Base0A, Priority::High)),
+    underlined: Some((true, Priority::High)),
+    ..Style::const_default()
+};
+
+pub const JUMP_LABEL_STYLE: Style = Style {
+    fg_color: Some((Base16Color::Base00, Priority::High)),
+    bg_color: Some((Base16Color::Base0A, Priority::High)),
+    bold: Some((true, Priority::High)),
+    ..Style::const_default()
+};
+
 pub const FG_COLOR: Base16Color = Base16Color::Base05;
 pub const BG_COLOR: Base16Color = Base16Color::Base00;
 
@@ -41,6 +60,8 @@ pub struct Style {
     pub underlined: Option<(bool, Priority)>,
     pub cursor: Option<CursorHalf>,
     pub is_hole: bool,
+    /// The innermost node that printed this text. Used to find what was clicked on.
+    pub node_id: Option<NodeId>,
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -195,6 +216,7 @@ impl ppp::Style for Style {
             bold: prioritize(outer.bold, inner.bold),
             underlined: prioritize(outer.underlined, inner.underlined),
             is_hole: outer.is_hole || inner.is_hole,
+            node_id: inner.node_id.or(outer.node_id),
         }
     }
 }
@@ -208,6 +230,7 @@ impl Style {
             underlined: None,
             cursor: None,
             is_hole: false,
+            node_id: None,
         }
     }
 
@@ -6,14 +6,14 @@ use std::fmt;
 use std::str::FromStr;
 
 // The node in this LocationInner may not be valid (may have been deleted!)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Bookmark(LocationInner);
 
 /// A location between nodes, or within text, where a cursor could go.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Location(LocationInner);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum LocationInner {
     /// The usize is an index between chars (so it can be equal to the len)
     InText(Node, usize),
@@ -75,6 +75,13 @@ impl Location {
         }
     }
 
+    /// If the node is texty, returns the location before the `char_index`'th character of its text
+    /// (clamped to the end of the text). Otherwise returns `None`.
+    pub fn in_text(s: &Storage, node: Node, char_index: usize) -> Option<Location> {
+        let text_len = node.text(s)?.num_chars();
+        Some(Location(InText(node, char_index.min(text_len))))
+    }
+
     /// If the node is texty, returns the location at the end of its text, otherwise returns `None`.
     pub fn end_of_text(s: &Storage, node: Node) -> Option<Location> {
         let text_len = node.text(s)?.num_chars();
@@ -267,6 +274,60 @@ impl Location {
         }
     }
 
+    /// Get the location at the next hole in document order, wrapping around to the start of the
+    /// document.
+    pub fn next_hole(self, s: &Storage) -> Option<Location> {
+        let (before, after) = self.holes_around(s);
+        let hole = after.first().or(before.first()).copied();
+        // If the cursor is on the only hole, stay there.
+        let hole = hole.or_else(|| self.node(s).filter(|_| self.is_at_hole(s)))?;
+        Some(Location(AtNode(hole)))
+    }
+
+    /// Get the location at the previous hole in document order, wrapping around to the end of
+    /// the document.
+    pub fn prev_hole(self, s: &Storage) -> Option<Location> {
+        let (before, after) = self.holes_around(s);
+        let hole = before.last().or(after.last()).copied();
+        // If the cursor is on the only hole, stay there.
+        let hole = hole.or_else(|| self.node(s).filter(|_| self.is_at_hole(s)))?;
+        Some(Location(AtNode(hole)))
+    }
+
+    /// All of the holes in the document (except any at this location), in document order, split
+    /// into those before and those after this location. A node comes before its descendants.
+    fn holes_around(self, s: &Storage) -> (Vec<Node>, Vec<Node>) {
+        let cursor_node = self.0.reference_node();
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut passed_cursor = false;
+        // A pre-order walk: a node's children are pushed last-first, so they're popped in order.
+        let mut stack = vec![self.root_node(s)];
+        while let Some(node) = stack.pop() {
+            if node == cursor_node {
+                passed_cursor = true;
+            } else if node.construct(s).is_hole(s) {
+                if passed_cursor {
+                    after.push(node);
+                } else {
+                    before.push(node);
+                }
+            }
+            let mut child = node.last_child(s);
+            while let Some(node) = child {
+                stack.push(node);
+                child = node.prev_sibling(s);
+            }
+        }
+        (before, after)
+    }
+
+    fn is_at_hole(self, s: &Storage) -> bool {
+        self.node(s)
+            .map(|node| node.construct(s).is_hole(s))
+            .unwrap_or(false)
+    }
+
     /// Get the location at this node's parent.
     pub fn parent(self, s: &Storage) -> Option<Location> {
         Some(Location(AtNode(self.parent_node(s)?)))
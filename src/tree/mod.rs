@@ -1,8 +1,10 @@
 mod forest;
 mod location;
 mod node;
+mod search;
 mod text;
 
 pub use location::{Bookmark, Location, Mode};
 pub(crate) use node::NodeForest;
 pub use node::{Node, NodeId};
+pub use search::{SearchError, SearchPattern, SearchQuery};
@@ -336,6 +336,32 @@ impl Node {
         Node(s.forest().root(self.0))
     }
 
+    /// Find the node with the given id among this node and its descendants.
+    pub fn find_descendant(self, s: &Storage, id: NodeId) -> Option<Node> {
+        if self.id(s) == id {
+            return Some(self);
+        }
+        let mut child = self.first_child(s);
+        while let Some(node) = child {
+            if let Some(found) = node.find_descendant(s, id) {
+                return Some(found);
+            }
+            child = node.next_sibling(s);
+        }
+        None
+    }
+
+    /// Count the holes among this node and its descendants.
+    pub fn num_holes(self, s: &Storage) -> usize {
+        let mut count = usize::from(self.construct(s).is_hole(s));
+        let mut child = self.first_child(s);
+        while let Some(node) = child {
+            count += node.num_holes(s);
+            child = node.next_sibling(s);
+        }
+        count
+    }
+
     /// Check whether this node has been deleted.
     pub fn is_valid(self, s: &Storage) -> bool {
         s.forest().is_valid(self.0)
@@ -346,7 +372,7 @@ impl Node {
      **************/
 
     /// Check if `other` is allowed where `self` currently is, according to our parent's arity.
-    fn accepts_replacement(self, s: &Storage, other: Node) -> bool {
+    pub fn accepts_replacement(self, s: &Storage, other: Node) -> bool {
         if let Some(parent) = s.forest().parent(self.0) {
             let sort = match Node(parent).arity(s) {
                 Arity::Fixed(sorts) => sorts.get(s, self.sibling_index(s)).bug(),
@@ -499,6 +525,57 @@ impl Node {
         Node(s.node_forest.forest.deep_copy(self.0, &mut clone_data))
     }
 
+    /// Make a deep copy of the given node, but with the given construct instead of its own. Its
+    /// text is kept if both constructs are texty, and its children are kept (in order) wherever the
+    /// new construct's arity allows. Any remaining slots are filled with holes. Returns the copy,
+    /// which will be a root, and the number of children that had to be dropped.
+    pub fn retyped_copy(self, s: &mut Storage, construct: Construct) -> (Node, usize) {
+        let new_node = Node::new(s, construct);
+        if let Some(text) = self.text(s).map(|text| text.as_str().to_owned()) {
+            if let Some(new_text) = new_node.text_mut(s) {
+                new_text.set(text);
+            }
+        }
+
+        let mut children = Vec::new();
+        let mut opt_child = self.first_child(s);
+        while let Some(child) = opt_child {
+            children.push(child);
+            opt_child = child.next_sibling(s);
+        }
+        let mut num_dropped = 0;
+        for child in children {
+            if child.construct(s).is_hole(s) {
+                continue;
+            }
+            let copy = child.deep_copy(s);
+            let placed = match construct.arity(s) {
+                Arity::Texty => false,
+                Arity::Listy(_) => new_node.insert_last_child(s, copy),
+                Arity::Fixed(_) => new_node.fill_first_hole(s, copy),
+            };
+            if !placed {
+                copy.delete_root(s);
+                num_dropped += 1;
+            }
+        }
+        (new_node, num_dropped)
+    }
+
+    /// Replace the first hole child of `self` that can be replaced by `new_child`. Returns false
+    /// and does nothing if there isn't one.
+    fn fill_first_hole(self, s: &mut Storage, new_child: Node) -> bool {
+        let mut opt_child = self.first_child(s);
+        while let Some(child) = opt_child {
+            if child.construct(s).is_hole(s) && child.swap(s, new_child) {
+                child.delete_root(s);
+                return true;
+            }
+            opt_child = child.next_sibling(s);
+        }
+        false
+    }
+
     /*************
      * Debugging *
      *************/
@@ -0,0 +1,148 @@
+use super::location::Location;
+use super::node::Node;
+use crate::language::Storage;
+use crate::util::{error, SynlessError};
+use regex::Regex;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SearchError {
+    #[error("Invalid regex in search: {0}")]
+    InvalidRegex(regex::Error),
+    #[error("Empty search query")]
+    EmptyQuery,
+    #[error("No search query")]
+    NoQuery,
+    #[error("No matches for '{0}'")]
+    NoMatches(String),
+}
+
+impl From<SearchError> for SynlessError {
+    fn from(error: SearchError) -> SynlessError {
+        error!(Edit, "{}", error)
+    }
+}
+
+/// What to look for when searching a document.
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+    /// Texty nodes whose text contains this string.
+    Substring(String),
+    /// Texty nodes whose text matches this regex (anywhere in the text).
+    Regex(Regex),
+    /// Nodes whose construct has this name.
+    Construct(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    /// The query as written by the user.
+    source: String,
+    pattern: SearchPattern,
+}
+
+impl SearchQuery {
+    /// Parse a query as typed by the user:
+    ///
+    /// - `/regex` matches text by regex,
+    /// - `:Name` matches nodes whose construct is named `Name`,
+    /// - anything else matches text containing it as a substring.
+    pub fn parse(query: &str) -> Result<SearchQuery, SearchError> {
+        let pattern = if let Some(regex) = query.strip_prefix('/') {
+            SearchPattern::Regex(Regex::new(regex).map_err(SearchError::InvalidRegex)?)
+        } else if let Some(name) = query.strip_prefix(':') {
+            SearchPattern::Construct(name.to_owned())
+        } else {
+            SearchPattern::Substring(query.to_owned())
+        };
+        SearchQuery::new(query.to_owned(), pattern)
+    }
+
+    pub fn new(source: String, pattern: SearchPattern) -> Result<SearchQuery, SearchError> {
+        let is_empty = match &pattern {
+            SearchPattern::Substring(string) | SearchPattern::Construct(string) => {
+                string.is_empty()
+            }
+            SearchPattern::Regex(regex) => regex.as_str().is_empty(),
+        };
+        if is_empty {
+            return Err(SearchError::EmptyQuery);
+        }
+        Ok(SearchQuery { source, pattern })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, s: &Storage, node: Node) -> bool {
+        match &self.pattern {
+            SearchPattern::Substring(string) => node
+                .text(s)
+                .map(|text| text.as_str().contains(string.as_str()))
+                .unwrap_or(false),
+            SearchPattern::Regex(regex) => node
+                .text(s)
+                .map(|text| regex.is_match(text.as_str()))
+                .unwrap_or(false),
+            SearchPattern::Construct(name) => node.construct(s).name(s) == name,
+        }
+    }
+
+    /// All nodes in the tree under `root` (but not `root` itself) that match, in document order.
+    pub fn find_all(&self, s: &Storage, root: Node) -> Vec<Node> {
+        let mut matches = Vec::new();
+        for_each_descendant(s, root, &mut |node| {
+            if self.matches(s, node) {
+                matches.push(node);
+            }
+        });
+        matches
+    }
+
+    /// The location of the first match after `loc` in document order, wrapping around to the start
+    /// of the document. Or the last match before it, if `backwards`.
+    pub fn find_from(&self, s: &Storage, loc: Location, backwards: bool) -> Option<Location> {
+        let root = loc.root_node(s);
+        // The node nearest to the cursor, whose position in document order we search from.
+        let cursor_node = loc
+            .node(s)
+            .or_else(|| loc.text_pos().map(|(node, _)| node))
+            .or_else(|| loc.parent_node(s))?;
+
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut passed_cursor = false;
+        for_each_descendant(s, root, &mut |node| {
+            if node == cursor_node {
+                passed_cursor = true;
+            } else if self.matches(s, node) {
+                if passed_cursor {
+                    after.push(node);
+                } else {
+                    before.push(node);
+                }
+            }
+        });
+
+        let found = if backwards {
+            before.last().or(after.last())
+        } else {
+            after.first().or(before.first())
+        };
+        // If the cursor is on the only match, stay there.
+        let found = found.copied().or_else(|| {
+            (cursor_node != root && self.matches(s, cursor_node)).then_some(cursor_node)
+        })?;
+        Some(Location::at(s, found))
+    }
+}
+
+/// Visit every node under `node` (but not `node` itself) in pre-order.
+fn for_each_descendant(s: &Storage, node: Node, visit: &mut impl FnMut(Node)) {
+    let mut child = node.first_child(s);
+    while let Some(node) = child {
+        visit(node);
+        for_each_descendant(s, node, visit);
+        child = node.next_sibling(s);
+    }
+}
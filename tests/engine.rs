@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use synless::{
+    parsing::JsonParser, ClipboardCommand, DocName, Engine, Node, Settings, TreeEdCommand,
+    TreeNavCommand,
+};
+
+const JSON_PATH: &str = "data/json_lang.ron";
+
+fn make_engine() -> (Engine, String) {
+    let mut engine = Engine::new(Settings::default());
+    let json_lang_ron = fs::read_to_string(JSON_PATH).unwrap();
+    let language_name = engine
+        .load_language_ron(Path::new(JSON_PATH), &json_lang_ron)
+        .unwrap();
+    engine.add_parser(&language_name, JsonParser);
+    (engine, language_name)
+}
+
+/// Load `source` as a json file named `name`, and make it the visible doc.
+fn open_doc(engine: &mut Engine, language_name: &str, name: &str, source: &str) -> DocName {
+    let doc_name = DocName::File(PathBuf::from(name));
+    engine
+        .load_doc_from_source(doc_name.clone(), language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+    doc_name
+}
+
+/// Move the cursor to the first node matching `query`.
+fn goto(engine: &mut Engine, query: &str) {
+    engine.set_search(query).unwrap();
+    engine.search_next(false).unwrap();
+    engine.clear_search();
+}
+
+/// Check that the doc prints as `after`, then as `before` after an undo, then as `after` again
+/// after a redo.
+fn assert_undo_redo(engine: &mut Engine, doc_name: &DocName, before: &str, after: &str) {
+    assert_eq!(engine.print_source(doc_name).unwrap(), after);
+    engine.undo().unwrap();
+    assert_eq!(engine.print_source(doc_name).unwrap(), before);
+    engine.redo().unwrap();
+    assert_eq!(engine.print_source(doc_name).unwrap(), after);
+}
+
+#[test]
+fn test_modified_after_undo() {
+    let (mut engine, language_name) = make_engine();
+    let doc_name = open_doc(&mut engine, &language_name, "a.json", "[1, 2, 3]");
+    let is_modified = |engine: &Engine| engine.get_doc(&doc_name).unwrap().is_modified();
+    assert!(!is_modified(&engine));
+
+    goto(&mut engine, "2");
+    engine.execute(TreeEdCommand::Delete).unwrap();
+    assert!(is_modified(&engine));
+    // Undoing back to the saved version makes the doc unmodified again
+    engine.undo().unwrap();
+    assert!(!is_modified(&engine));
+    engine.redo().unwrap();
+    assert!(is_modified(&engine));
+
+    engine.mark_saved(&doc_name).unwrap();
+    assert!(!is_modified(&engine));
+    engine.undo().unwrap();
+    assert!(is_modified(&engine));
+    engine.redo().unwrap();
+    assert!(!is_modified(&engine));
+    assert_eq!(engine.print_source(&doc_name).unwrap(), "[1, 3]");
+
+    // After undoing past the save and making a different edit, there's no way back to the save.
+    engine.undo().unwrap();
+    goto(&mut engine, "3");
+    engine.execute(TreeEdCommand::Delete).unwrap();
+    assert!(is_modified(&engine));
+    engine.undo().unwrap();
+    assert!(is_modified(&engine));
+    assert!(engine.redo().is_ok());
+    assert_eq!(engine.print_source(&doc_name).unwrap(), "[1, 2]");
+    assert!(is_modified(&engine));
+}
+
+#[test]
+fn test_recent_docs() {
+    let (mut engine, language_name) = make_engine();
+    let doc_a = open_doc(&mut engine, &language_name, "a.json", "[]");
+    let doc_b = open_doc(&mut engine, &language_name, "b.json", "[]");
+    let doc_c = open_doc(&mut engine, &language_name, "c.json", "[]");
+    let aux_doc = DocName::Auxilliary("aux".to_owned());
+    engine
+        .load_doc_from_source(aux_doc.clone(), &language_name, "[]")
+        .unwrap();
+    assert_eq!(engine.file_docs(), [&doc_c, &doc_b, &doc_a]);
+
+    // Only file docs are listed, from most to least recently visible
+    engine.set_visible_doc(&aux_doc).unwrap();
+    engine.set_visible_doc(&doc_a).unwrap();
+    assert_eq!(engine.file_docs(), [&doc_a, &doc_c, &doc_b]);
+
+    // Closing the visible doc shows the one that was visible before it
+    engine.delete_doc(&doc_a).unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&aux_doc));
+    engine.delete_doc(&aux_doc).unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_c));
+    assert_eq!(engine.file_docs(), [&doc_c, &doc_b]);
+}
+
+#[test]
+fn test_split_focus() {
+    let (mut engine, language_name) = make_engine();
+    let doc_a = open_doc(&mut engine, &language_name, "a.json", "[1, 2, 3]");
+    assert!(engine.switch_split_focus().is_err());
+
+    // Both halves show the same doc, each with its own cursor
+    goto(&mut engine, "1");
+    engine.split().unwrap();
+    goto(&mut engine, "3");
+    engine.switch_split_focus().unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "1");
+    engine.switch_split_focus().unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+
+    // Switching docs only changes the focused half
+    let doc_b = open_doc(&mut engine, &language_name, "b.json", "[4]");
+    engine.switch_split_focus().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_a));
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "1");
+    engine.switch_split_focus().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_b));
+
+    // Closing the doc in the unfocused half unsplits the screen
+    engine.delete_doc(&doc_a).unwrap();
+    assert!(!engine.is_split());
+    assert_eq!(engine.visible_doc_name(), Some(&doc_b));
+}
+
+#[test]
+fn test_wrap_and_raise() {
+    let (mut engine, language_name) = make_engine();
+    let doc_name = open_doc(&mut engine, &language_name, "a.json", "[1, [2, 3]]");
+    let language = engine.get_language(&language_name).unwrap();
+    let array = language.construct(engine.raw_storage(), "Array").unwrap();
+    let number = language.construct(engine.raw_storage(), "Number").unwrap();
+
+    goto(&mut engine, "1");
+    engine.execute(TreeEdCommand::Wrap(array)).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "[1]");
+    assert_undo_redo(&mut engine, &doc_name, "[1, [2, 3]]", "[[1], [2, 3]]");
+
+    goto(&mut engine, "1");
+    engine.execute(TreeEdCommand::Raise).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "1");
+    assert_undo_redo(&mut engine, &doc_name, "[[1], [2, 3]]", "[1, [2, 3]]");
+
+    // Unwrap keeps the first child, discarding the rest
+    goto(&mut engine, "2");
+    engine.execute(TreeNavCommand::Parent).unwrap();
+    engine.execute(TreeEdCommand::Unwrap).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "2");
+    assert_undo_redo(&mut engine, &doc_name, "[1, [2, 3]]", "[1, 2]");
+
+    // The wrapper's existing children stay put
+    let s = engine.raw_storage_mut();
+    let zero = Node::with_text(s, number, "0".to_owned()).unwrap();
+    let wrapper = Node::with_children(s, array, [zero]).unwrap();
+    goto(&mut engine, "2");
+    engine.execute(TreeEdCommand::WrapIn(wrapper, 1)).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "[0, 2]");
+    assert_undo_redo(&mut engine, &doc_name, "[1, 2]", "[1, [0, 2]]");
+
+    // Can't raise the top-level value, or unwrap a node without children
+    goto(&mut engine, "1");
+    assert!(engine.execute(TreeEdCommand::Unwrap).is_err());
+    engine.execute(TreeEdCommand::Raise).unwrap();
+    assert!(engine.execute(TreeEdCommand::Raise).is_err());
+    assert_eq!(engine.print_source(&doc_name).unwrap(), "1");
+}
+
+#[test]
+fn test_move_nodes() {
+    let (mut engine, language_name) = make_engine();
+    let doc_name = open_doc(
+        &mut engine,
+        &language_name,
+        "a.json",
+        "[1, 2, 3, {\"a\": 4}]",
+    );
+
+    goto(&mut engine, "2");
+    engine.execute(TreeEdCommand::MoveLeft).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "2");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        "[1, 2, 3, {\"a\": 4}]",
+        "[2, 1, 3, {\"a\": 4}]",
+    );
+    goto(&mut engine, "2");
+    assert!(engine.execute(TreeEdCommand::MoveLeft).is_err());
+
+    engine.execute(TreeEdCommand::MoveRight).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "2");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        "[2, 1, 3, {\"a\": 4}]",
+        "[1, 2, 3, {\"a\": 4}]",
+    );
+
+    goto(&mut engine, "2");
+    engine.execute(TreeEdCommand::MoveLast).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "2");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        "[1, 2, 3, {\"a\": 4}]",
+        "[1, 3, {\"a\": 4}, 2]",
+    );
+    goto(&mut engine, "2");
+    assert!(engine.execute(TreeEdCommand::MoveRight).is_err());
+    assert!(engine.execute(TreeEdCommand::MoveLast).is_err());
+
+    goto(&mut engine, "3");
+    engine.execute(TreeEdCommand::MoveFirst).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        "[1, 3, {\"a\": 4}, 2]",
+        "[3, 1, {\"a\": 4}, 2]",
+    );
+
+    goto(&mut engine, "3");
+    engine.execute(TreeEdCommand::MoveTo(2)).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        "[3, 1, {\"a\": 4}, 2]",
+        "[1, {\"a\": 4}, 3, 2]",
+    );
+    goto(&mut engine, "3");
+    assert!(engine.execute(TreeEdCommand::MoveTo(4)).is_err());
+    assert!(engine.execute(TreeEdCommand::MoveTo(2)).is_err());
+
+    // Only nodes in listy sequences can move
+    goto(&mut engine, "4");
+    assert!(engine.execute(TreeEdCommand::MoveLeft).is_err());
+    assert!(engine.execute(TreeEdCommand::MoveFirst).is_err());
+    assert_eq!(
+        engine.print_source(&doc_name).unwrap(),
+        "[1, {\"a\": 4}, 3, 2]"
+    );
+}
+
+#[test]
+fn test_selection_edits() {
+    let (mut engine, language_name) = make_engine();
+    let source = "{\"a\": [1, 2, 3, 4], \"b\": 5}";
+    let doc_name = open_doc(&mut engine, &language_name, "a.json", source);
+    let language = engine.get_language(&language_name).unwrap();
+    let array = language.construct(engine.raw_storage(), "Array").unwrap();
+    let object = language.construct(engine.raw_storage(), "Object").unwrap();
+    let select_2_and_3 = |engine: &mut Engine| {
+        goto(engine, "2");
+        engine.toggle_selection().unwrap();
+        engine.execute(TreeNavCommand::Next).unwrap();
+    };
+
+    select_2_and_3(&mut engine);
+    engine.execute(TreeEdCommand::Delete).unwrap();
+    assert_undo_redo(&mut engine, &doc_name, source, "{\"a\": [1, 4], \"b\": 5}");
+    engine.undo().unwrap();
+
+    select_2_and_3(&mut engine);
+    engine.execute(TreeEdCommand::Wrap(array)).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "[2, 3]");
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        source,
+        "{\"a\": [1, [2, 3], 4], \"b\": 5}",
+    );
+    engine.undo().unwrap();
+
+    // Numbers can't go in an object
+    select_2_and_3(&mut engine);
+    assert!(engine.execute(TreeEdCommand::Wrap(object)).is_err());
+    assert_eq!(engine.print_source(&doc_name).unwrap(), source);
+    engine.toggle_selection().unwrap();
+
+    goto(&mut engine, "5");
+    engine.execute(ClipboardCommand::Copy).unwrap();
+    select_2_and_3(&mut engine);
+    engine.execute(ClipboardCommand::Paste).unwrap();
+    assert_undo_redo(
+        &mut engine,
+        &doc_name,
+        source,
+        "{\"a\": [1, 5, 4], \"b\": 5}",
+    );
+    engine.undo().unwrap();
+
+    // A failed paste leaves both the doc and the clipboard unchanged
+    goto(&mut engine, "b");
+    engine.execute(TreeNavCommand::Parent).unwrap();
+    engine.execute(ClipboardCommand::Copy).unwrap();
+    select_2_and_3(&mut engine);
+    assert!(engine.execute(ClipboardCommand::Paste).is_err());
+    assert_eq!(engine.print_source(&doc_name).unwrap(), source);
+    goto(&mut engine, "b");
+    engine.execute(TreeNavCommand::Parent).unwrap();
+    engine.execute(ClipboardCommand::Paste).unwrap();
+    assert_eq!(
+        engine.print_source(&doc_name).unwrap(),
+        "{\"a\": [1, 2, 3, 4], \"b\": 5, \"b\": 5}"
+    );
+}
+
+#[test]
+fn test_registers() {
+    let (mut engine, language_name) = make_engine();
+    let source = "[1, {\"k\": 2}]";
+    let doc_name = open_doc(&mut engine, &language_name, "a.json", source);
+    assert!(engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .is_err());
+
+    // The register keeps its contents after pasting
+    goto(&mut engine, "1");
+    engine
+        .execute(ClipboardCommand::CopyToRegister('a'))
+        .unwrap();
+    engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .unwrap();
+    engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .unwrap();
+    assert_eq!(
+        engine.print_source(&doc_name).unwrap(),
+        "[1, 1, 1, {\"k\": 2}]"
+    );
+    // Both pastes are in the same undo group
+    engine.undo().unwrap();
+    assert_eq!(engine.print_source(&doc_name).unwrap(), source);
+
+    // A number can't be a key
+    goto(&mut engine, "k");
+    assert!(engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .is_err());
+    assert_eq!(engine.print_source(&doc_name).unwrap(), source);
+    goto(&mut engine, "2");
+    engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .unwrap();
+    assert_undo_redo(&mut engine, &doc_name, source, "[1, {\"k\": 1}]");
+
+    // Copying to a register replaces its contents, and doesn't touch the other registers
+    goto(&mut engine, "k");
+    engine.execute(TreeNavCommand::Parent).unwrap();
+    engine
+        .execute(ClipboardCommand::CopyToRegister('b'))
+        .unwrap();
+    engine.execute(TreeNavCommand::Parent).unwrap();
+    engine
+        .execute(ClipboardCommand::CopyToRegister('a'))
+        .unwrap();
+    engine
+        .execute(ClipboardCommand::PasteFromRegister('a'))
+        .unwrap();
+    engine.execute(TreeNavCommand::FirstChild).unwrap();
+    engine
+        .execute(ClipboardCommand::PasteFromRegister('b'))
+        .unwrap();
+    assert_eq!(
+        engine.print_source(&doc_name).unwrap(),
+        "[1, {\"k\": 1}, {\"k\": 1, \"k\": 1}]"
+    );
+}
+
+#[test]
+fn test_paste_source_into_listy_root() {
+    let lang_ron = r#"
+        LanguageSpec(
+            name: "signs",
+            file_extensions: [],
+            grammar: GrammarSpec(
+                constructs: [
+                    ConstructSpec(name: "Root", arity: Listy(SortSpec(["Plus", "Minus"]))),
+                    ConstructSpec(name: "Plus", arity: Fixed([])),
+                    ConstructSpec(name: "Minus", arity: Fixed([])),
+                ],
+                sorts: [],
+                root_construct: "Root",
+            ),
+            default_display_notation: "Source",
+            default_source_notation: Some("Source"),
+            notations: [
+                NotationSetSpec(
+                    name: "Source",
+                    notations: [
+                        ("Root", Fold(first: Child(0), join: Concat(Left, Right))),
+                        ("Plus", Literal("+")),
+                        ("Minus", Literal("-")),
+                    ],
+                ),
+            ],
+        )"#;
+    let mut engine = Engine::new(Settings::default());
+    let language_name = engine
+        .load_language_ron(Path::new("signs.ron"), lang_ron)
+        .unwrap();
+    let doc_name = open_doc(&mut engine, &language_name, "a.signs", "-");
+
+    // Every top-level node is pasted, in order
+    engine.paste_source("+-++").unwrap();
+    assert_undo_redo(&mut engine, &doc_name, "-", "+-++-");
+}
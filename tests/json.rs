@@ -1,18 +1,25 @@
 use std::fs;
 use std::path::Path;
-use synless::{parsing::JsonParser, DocName, Engine, Settings};
+use synless::{
+    parsing::{JsonParser, JsoncParser},
+    DocName, Engine, Location, Settings,
+};
 
 const JSON_PATH: &str = "data/json_lang.ron";
 
-#[test]
-fn test_json() {
+fn make_engine() -> (Engine, String) {
     let mut engine = Engine::new(Settings::default());
-
     let json_lang_ron = fs::read_to_string(JSON_PATH).unwrap();
     let language_name = engine
         .load_language_ron(Path::new(JSON_PATH), &json_lang_ron)
         .unwrap();
     engine.add_parser(&language_name, JsonParser);
+    (engine, language_name)
+}
+
+#[test]
+fn test_json() {
+    let (mut engine, language_name) = make_engine();
 
     let doc_name = DocName::Auxilliary("<testing>".to_owned());
     let source = "{\"primitives\": [true, false, null, 5.3, \"string!\"]}";
@@ -22,3 +29,384 @@ fn test_json() {
     let output = engine.print_source(&doc_name).unwrap();
     assert_eq!(output, source);
 }
+
+#[test]
+fn test_json_recovery() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "[1, , foo, {\"a\": }, \"b";
+    let errors = engine
+        .load_doc_from_source_with_recovery(doc_name.clone(), &language_name, source)
+        .unwrap()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "In auxilliary:<testing> at 1:5: Expected a value",
+            "In auxilliary:<testing> at 1:7: Invalid value 'foo'",
+            "In auxilliary:<testing> at 1:18: Expected a value",
+            "In auxilliary:<testing> at 1:21: Unterminated string",
+            "In auxilliary:<testing> at 1:1: Unclosed '['",
+        ]
+    );
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, "[1, foo, {\"a\": ☐}, \"b\"]");
+}
+
+#[test]
+fn test_json_unicode_escapes() {
+    let (mut engine, language_name) = make_engine();
+
+    // Characters outside the BMP are escaped as surrogate pairs
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = r#"["\ud83d\ude00", "caf\u00e9"]"#;
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, "[\"😀\", \"café\"]");
+
+    // A short escape doesn't swallow the closing quote, and neither does a lone surrogate
+    let doc_name = DocName::Auxilliary("<invalid>".to_owned());
+    let source = r#"["\u12", "\ud83d"]"#;
+    let errors = engine
+        .load_doc_from_source_with_recovery(doc_name.clone(), &language_name, source)
+        .unwrap()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "In auxilliary:<invalid> at 1:7: Invalid escape '\\u12'",
+            "In auxilliary:<invalid> at 1:17: Invalid escape '\\ud83d'",
+        ]
+    );
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, source);
+}
+
+#[test]
+fn test_jsonc_round_trip() {
+    let (mut engine, language_name) = make_engine();
+    engine.add_parser(&language_name, JsoncParser);
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = r#"{
+    // Editor settings
+    "tabs": false,
+    "width": 4,
+
+    /* Files to skip */
+    "ignore": [
+        "target",
+        // Not checked in
+        "scratch"
+    ]
+}
+"#;
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, source);
+}
+
+#[test]
+fn test_strict_json() {
+    let (mut engine, language_name) = make_engine();
+
+    let source = "[1, // one\n 2,]";
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    assert!(engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .is_err());
+    let errors = engine
+        .load_doc_from_source_with_recovery(doc_name.clone(), &language_name, source)
+        .unwrap()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "In auxilliary:<testing> at 1:5: Comments are only allowed in JSONC",
+            "In auxilliary:<testing> at 2:3: Trailing commas are only allowed in JSONC",
+        ]
+    );
+    // The comment is kept anyways
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, "[\n    1,\n    // one\n    2\n]");
+
+    // JSONC is allowed in .jsonc files
+    let jsonc_name = DocName::File("settings.jsonc".into());
+    engine
+        .load_doc_from_source(jsonc_name, &language_name, source)
+        .unwrap();
+}
+
+#[test]
+fn test_jsonc_comment_placement() {
+    let (mut engine, language_name) = make_engine();
+    engine.add_parser(&language_name, JsoncParser);
+
+    // Comments that have no place in the tree are moved to the nearest list
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = r#"// Header
+{
+    "a" /* key */: // value
+        1
+}
+// Footer
+"#;
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let output = engine.print_source(&doc_name).unwrap();
+    assert_eq!(
+        output,
+        r#"{
+    // Header
+    /* key */
+    // value
+    "a": 1
+    // Footer
+}
+"#
+    );
+
+    // Or are errors, if there is no list
+    let doc_name = DocName::Auxilliary("<scalar>".to_owned());
+    let errors = engine
+        .load_doc_from_source_with_recovery(doc_name, &language_name, "// Header\n5")
+        .unwrap()
+        .into_iter()
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec!["In auxilliary:<scalar> at 1:1: Comments are only allowed inside an array or object"]
+    );
+}
+
+#[test]
+fn test_search() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "{\"ap\": \"apple\", \"b\": [\"banana\", \"apricot\", 3]}";
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+
+    engine.set_search("ap").unwrap();
+    assert_eq!(engine.num_search_matches(), 3);
+    let mut found = Vec::new();
+    for _ in 0..4 {
+        engine.search_next(false).unwrap();
+        found.push(engine.print_source_at_cursor().unwrap());
+    }
+    assert_eq!(found, ["\"ap\"", "\"apple\"", "\"apricot\"", "\"ap\""]);
+    engine.search_next(true).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "\"apricot\"");
+
+    engine.set_search("/^b").unwrap();
+    assert_eq!(engine.num_search_matches(), 2);
+
+    engine.set_search(":Number").unwrap();
+    engine.search_next(false).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+
+    engine.set_search("zzz").unwrap();
+    assert!(engine.search_next(false).is_err());
+    assert!(engine.set_search("/(").is_err());
+}
+
+#[test]
+fn test_replace_all() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "[{\"id\": 5, \"name\": \"x\"}, {\"id\": 7}, {\"other\": 1}]";
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+
+    let pattern = "(ObjectPair (Key \"id\") (Number $v))";
+    let template = "(ObjectPair (Key \"id\") (String $v))";
+    assert_eq!(
+        engine.preview_replacements(pattern, template).unwrap(),
+        [
+            ("\"id\": 5".to_owned(), "\"id\": \"5\"".to_owned()),
+            ("\"id\": 7".to_owned(), "\"id\": \"7\"".to_owned()),
+        ]
+    );
+    assert_eq!(
+        engine
+            .preview_replacements("(Object $first ..)", "(Object $first)")
+            .unwrap()
+            .len(),
+        3
+    );
+    assert!(engine.replace_all(pattern, "(String $w)").is_err());
+
+    assert_eq!(engine.replace_all(pattern, template).unwrap(), 2);
+    assert_eq!(
+        engine.print_source(&doc_name).unwrap(),
+        "[{\"id\": \"5\", \"name\": \"x\"}, {\"id\": \"7\"}, {\"other\": 1}]"
+    );
+    engine.undo().unwrap();
+    assert_eq!(engine.print_source(&doc_name).unwrap(), source);
+}
+
+#[test]
+fn test_goto_line() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = r#"{
+    // Editor settings
+    "tabs": false,
+    "width": 4,
+
+    /* Files to skip */
+    "ignore": [
+        "target",
+        // Not checked in
+        "scratch"
+    ]
+}
+"#;
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+    assert!(engine.goto_line(3).is_err());
+    assert_eq!(engine.line_number_at_cursor(), None);
+
+    // Only saving records line numbers, not printing (e.g. for autosave).
+    engine.print_source(&doc_name).unwrap();
+    assert!(engine.goto_line(3).is_err());
+
+    engine.print_source_and_record_lines(&doc_name).unwrap();
+    engine.goto_line(3).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "\"tabs\": false");
+    assert_eq!(engine.line_number_at_cursor(), Some(3));
+    engine.goto_line(8).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "\"target\"");
+    // Nothing starts on the last line, so go to the closest line before it that has something.
+    engine.goto_line(12).unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "\"scratch\"");
+    assert_eq!(engine.line_number_at_cursor(), Some(10));
+}
+
+#[test]
+fn test_jump_list() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_a = DocName::Auxilliary("<a>".to_owned());
+    let doc_b = DocName::Auxilliary("<b>".to_owned());
+    engine
+        .load_doc_from_source(doc_a.clone(), &language_name, "[1, 2, 3]")
+        .unwrap();
+    engine
+        .load_doc_from_source(doc_b.clone(), &language_name, "{}")
+        .unwrap();
+    engine.set_visible_doc(&doc_a).unwrap();
+    assert!(engine.jump_back().is_err());
+
+    engine.set_search("3").unwrap();
+    engine.search_next(false).unwrap();
+    engine.set_visible_doc(&doc_b).unwrap();
+
+    engine.jump_back().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_a));
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+    engine.jump_back().unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "[1, 2, 3]");
+    assert!(engine.jump_back().is_err());
+    engine.jump_forward().unwrap();
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "3");
+    engine.jump_forward().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_b));
+    assert!(engine.jump_forward().is_err());
+
+    // Positions whose node was deleted are skipped.
+    engine.set_visible_doc(&doc_a).unwrap();
+    assert_eq!(
+        engine
+            .replace_all("(Number \"3\")", "(Number \"4\")")
+            .unwrap(),
+        1
+    );
+    engine.jump_back().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_b));
+    engine.jump_back().unwrap();
+    assert_eq!(engine.visible_doc_name(), Some(&doc_a));
+    assert_eq!(engine.print_source_at_cursor().unwrap(), "[1, 2, 4]");
+}
+
+#[test]
+fn test_hole_navigation() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "{\"a\": , \"b\": [1, 2], \"c\": }";
+    engine
+        .load_doc_from_source_with_recovery(doc_name.clone(), &language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+    assert_eq!(engine.num_holes(), Some(2));
+
+    let s = engine.raw_storage();
+    let start = engine.visible_doc().unwrap().cursor();
+    let first = start.next_hole(s).unwrap();
+    let second = first.next_hole(s).unwrap();
+    assert_ne!(first, second);
+    let key = |loc: Location| loc.node(s).unwrap().prev_sibling(s).unwrap();
+    assert_eq!(key(first).text(s).unwrap().as_str(), "a");
+    assert_eq!(key(second).text(s).unwrap().as_str(), "c");
+    // Wraps around in both directions
+    assert_eq!(second.next_hole(s), Some(first));
+    assert_eq!(first.prev_hole(s), Some(second));
+    assert_eq!(start.prev_hole(s), Some(second));
+}
+
+#[test]
+fn test_hole_navigation_from_inside_subtree() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "{\"a\": , \"b\": {\"c\": }}";
+    engine
+        .load_doc_from_source_with_recovery(doc_name.clone(), &language_name, source)
+        .unwrap();
+    engine.set_visible_doc(&doc_name).unwrap();
+
+    let s = engine.raw_storage();
+    let root = engine.visible_doc().unwrap().cursor().root_node(s);
+    let pair_a = root.first_child(s).unwrap().first_child(s).unwrap();
+    let pair_b = pair_a.next_sibling(s).unwrap();
+    let key_b = pair_b.first_child(s).unwrap();
+    let inner_object = pair_b.last_child(s).unwrap();
+    let hole_a = Location::at(s, pair_a.last_child(s).unwrap());
+    let hole_c = Location::at(
+        s,
+        inner_object.first_child(s).unwrap().last_child(s).unwrap(),
+    );
+
+    // Holes inside the node at the cursor come after it, in document order
+    for start in [
+        Location::at(s, pair_b),
+        Location::in_text(s, key_b, 0).unwrap(),
+        Location::before_children(s, inner_object).unwrap(),
+    ] {
+        assert_eq!(start.next_hole(s), Some(hole_c));
+        assert_eq!(start.prev_hole(s), Some(hole_a));
+    }
+}
@@ -0,0 +1,19 @@
+use synless::KeyMacro;
+
+#[test]
+fn test_key_macro_round_trip() {
+    let keys = "j j C-s space A-enter S-tab @";
+    let key_macro = keys.parse::<KeyMacro>().unwrap();
+    assert_eq!(key_macro.len(), 7);
+    assert_eq!(key_macro.to_string(), keys);
+    assert_eq!(
+        key_macro.to_string().parse::<KeyMacro>().unwrap(),
+        key_macro
+    );
+
+    assert_eq!("".parse::<KeyMacro>().unwrap(), KeyMacro::default());
+    assert_eq!(
+        "j C-nope".parse::<KeyMacro>().unwrap_err().message,
+        "Invalid key 'C-nope' in macro"
+    );
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::Path;
+use synless::{parsing::JsonParser, DocName, Engine, Settings};
+
+const JSON_PATH: &str = "data/json_lang.ron";
+
+fn make_json_engine(use_json_parser: bool) -> (Engine, String) {
+    let mut engine = Engine::new(Settings::default());
+    let json_lang_ron = fs::read_to_string(JSON_PATH).unwrap();
+    let language_name = engine
+        .load_language_ron(Path::new(JSON_PATH), &json_lang_ron)
+        .unwrap();
+    if use_json_parser {
+        engine.add_parser(&language_name, JsonParser);
+    }
+    (engine, language_name)
+}
+
+#[test]
+fn test_notation_parser_json() {
+    let source = r#"
+        {"primitives": [true, false, null, 5.3, "string!", ""],
+         "nested": [[], {}, [{"a":-1}]],
+         "escaped": "a\/b\tc \u0041"
+        }"#;
+
+    let (mut engine, language_name) = make_json_engine(false);
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let output = engine.print_source(&doc_name).unwrap();
+
+    // Must agree with the hand-written json parser
+    let (mut json_engine, language_name) = make_json_engine(true);
+    json_engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let expected = json_engine.print_source(&doc_name).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(
+        engine.print_sexpr(&doc_name).unwrap(),
+        json_engine.print_sexpr(&doc_name).unwrap()
+    );
+
+    // Parsing the printed source gives the same document back
+    let copy_name = DocName::Auxilliary("<copy>".to_owned());
+    engine
+        .load_doc_from_source(copy_name.clone(), &language_name, &output)
+        .unwrap();
+    assert_eq!(engine.print_source(&copy_name).unwrap(), output);
+}
+
+#[test]
+fn test_notation_parser_words_and_escapes() {
+    let (mut engine, language_name) = make_json_engine(false);
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+
+    // `true` doesn't match the start of `trueish`, so that's read as a number instead
+    let source = r#"[trueish, true, "say \"hi\""]"#;
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    assert_eq!(
+        engine.print_sexpr(&doc_name).unwrap(),
+        r#"(Root (Array (Number "trueish") (True) (String "say \"hi\"")))"#
+    );
+
+    let bad_name = DocName::Auxilliary("<bad>".to_owned());
+    let message = engine
+        .load_doc_from_source(bad_name, &language_name, r#"["\q"]"#)
+        .unwrap_err()
+        .message;
+    assert_eq!(message, r"In auxilliary:<bad> at 1:3: Invalid escape '\q'");
+}
+
+#[test]
+fn test_notation_parser_errors() {
+    let (mut engine, language_name) = make_json_engine(false);
+    let mut load = |source: &str| {
+        let doc_name = DocName::Auxilliary("<testing>".to_owned());
+        engine
+            .load_doc_from_source(doc_name, &language_name, source)
+            .unwrap_err()
+            .message
+    };
+
+    assert_eq!(
+        load("[1, 2"),
+        "In auxilliary:<testing> at 1:6: Expected one of ',', ']'"
+    );
+    assert_eq!(
+        load("{\"a\" 1}"),
+        "In auxilliary:<testing> at 1:6: Expected ':'"
+    );
+    assert_eq!(
+        load("[1] 2"),
+        "In auxilliary:<testing> at 1:5: Expected end of file"
+    );
+}
+
+#[test]
+fn test_notation_parser_ambiguous() {
+    let lang_ron = r#"
+        LanguageSpec(
+            name: "ambiguous",
+            file_extensions: [],
+            grammar: GrammarSpec(
+                constructs: [
+                    ConstructSpec(name: "Root", arity: Listy(SortSpec(["A", "B"]))),
+                    ConstructSpec(name: "A", arity: Fixed([])),
+                    ConstructSpec(name: "B", arity: Fixed([])),
+                ],
+                sorts: [],
+                root_construct: "Root",
+            ),
+            default_display_notation: "Source",
+            default_source_notation: Some("Source"),
+            notations: [
+                NotationSetSpec(
+                    name: "Source",
+                    notations: [
+                        ("Root", Fold(first: Child(0), join: Concat(Left, Right))),
+                        ("A", Literal("x")),
+                        ("B", Concat(Literal("x"), Literal("y"))),
+                    ],
+                ),
+            ],
+        )"#;
+    let mut engine = Engine::new(Settings::default());
+    let language_name = engine
+        .load_language_ron(Path::new("ambiguous.ron"), lang_ron)
+        .unwrap();
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let message = engine
+        .load_doc_from_source(doc_name, &language_name, "x xy")
+        .unwrap_err()
+        .message;
+    assert_eq!(
+        message,
+        "In auxilliary:<testing>: Language 'ambiguous' is too ambiguous to parse from its source \
+         notation: constructs 'A' and 'B' can both start with 'x'"
+    );
+}
@@ -0,0 +1,456 @@
+use partial_pretty_printer as ppp;
+use std::cell::{RefCell, RefMut};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+use synless::{ColorTheme, DocName, Event, Frontend, Key, Runtime, Settings, Style};
+
+const BASE_MODULE_PATH: &str = "scripts/base_module.rhai";
+const INTERNALS_MODULE_PATH: &str = "scripts/internals_module.rhai";
+const INIT_PATH: &str = "scripts/init.rhai";
+const MAIN_PATH: &str = "scripts/main.rhai";
+
+/// Bind F12 to end the test's main loop, in every mode.
+const TEST_LAYER: &str = r#"
+    let keymap = new_keymap();
+    keymap.bind_key("F12", "EndTest", || exit());
+    let layer = new_layer("test");
+    layer.add_mode_keymap("Tree", keymap);
+    layer.add_mode_keymap("Text", keymap);
+    s::register_layer(layer);
+    s::add_global_layer("test");
+"#;
+
+/// A frontend with no screen, that receives the events in its queue.
+struct TestFrontend {
+    events: Rc<RefCell<VecDeque<Event>>>,
+}
+
+impl ppp::pane::PrettyWindow for TestFrontend {
+    type Error = Infallible;
+    type Style = Style;
+
+    fn size(&self) -> Result<ppp::Size, Infallible> {
+        Ok(ppp::Size {
+            width: 80,
+            height: 24,
+        })
+    }
+
+    fn display_char(
+        &mut self,
+        _ch: char,
+        _pos: ppp::Pos,
+        _style: &Style,
+        _full_width: bool,
+    ) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_focus(&mut self, _pos: ppp::Pos) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+impl Frontend for TestFrontend {
+    fn set_color_theme(&mut self, _theme: ColorTheme) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn next_event(&mut self, _timeout: Duration) -> Result<Option<Event>, Infallible> {
+        let event = self.events.borrow_mut().pop_front();
+        match event {
+            Some(event) => Ok(Some(event)),
+            None => panic!("Ran out of test events"),
+        }
+    }
+
+    fn start_frame(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn end_frame(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_clipboard(&mut self, _text: &str) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// The editor, running the real scripts, with its own directory for files and recovery files.
+struct TestEditor {
+    dir: PathBuf,
+    settings: Settings,
+    events: Rc<RefCell<VecDeque<Event>>>,
+    runtime: Rc<RefCell<Runtime<TestFrontend>>>,
+    engine: rhai::Engine,
+    main_ast: rhai::AST,
+}
+
+impl TestEditor {
+    fn new(test_name: &str) -> TestEditor {
+        TestEditor::with_settings(test_name, Settings::default())
+    }
+
+    fn with_settings(test_name: &str, mut settings: Settings) -> TestEditor {
+        let dir =
+            std::env::temp_dir().join(format!("synless_test_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        settings.recovery_dir = dir.join("recovery");
+
+        let events = Rc::new(RefCell::new(VecDeque::new()));
+        let (runtime, engine, main_ast) = start_runtime(settings.clone(), events.clone());
+        TestEditor {
+            dir,
+            settings,
+            events,
+            runtime,
+            engine,
+            main_ast,
+        }
+    }
+
+    /// Start over with a fresh runtime, as if the editor had crashed and been reopened.
+    fn restart(&mut self) {
+        (self.runtime, self.engine, self.main_ast) =
+            start_runtime(self.settings.clone(), self.events.clone());
+    }
+
+    fn runtime(&self) -> RefMut<Runtime<TestFrontend>> {
+        self.runtime.borrow_mut()
+    }
+
+    /// Press each of the space-separated `keys`, running main.rhai until they've all been
+    /// handled.
+    fn press(&self, keys: &str) {
+        {
+            let mut events = self.events.borrow_mut();
+            for key in keys.split_whitespace().chain(["F12"]) {
+                events.push_back(Event::Key(Key::from_str(key).unwrap()));
+            }
+        }
+        match self.engine.run_ast(&self.main_ast) {
+            Ok(()) => (),
+            Err(err) if matches!(*err, rhai::EvalAltResult::Exit(..)) => (),
+            Err(err) => panic!("main.rhai failed: {err}"),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Write `source` to the file `name`, and open it.
+    fn open(&self, name: &str, source: &str) -> PathBuf {
+        let path = self.path(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, source).unwrap();
+        self.runtime().open_doc(path_str(&path)).unwrap();
+        path
+    }
+
+    fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.path(name)).unwrap()
+    }
+
+    /// The names of the files in the recovery directory, sorted.
+    fn recovery_files(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.settings.recovery_dir) else {
+            return Vec::new();
+        };
+        let mut names = entries
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    fn open_docs(&self) -> Vec<String> {
+        self.runtime()
+            .list_docs()
+            .into_iter()
+            .map(|doc| doc.into_string().unwrap())
+            .collect()
+    }
+}
+
+impl Drop for TestEditor {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Set up a runtime and rhai engine the way main.rs does, with the test layer added.
+fn start_runtime(
+    settings: Settings,
+    events: Rc<RefCell<VecDeque<Event>>>,
+) -> (Rc<RefCell<Runtime<TestFrontend>>>, rhai::Engine, rhai::AST) {
+    let runtime = Rc::new(RefCell::new(Runtime::new(
+        settings,
+        TestFrontend { events },
+    )));
+
+    let mut engine = rhai::Engine::new();
+    engine.set_fail_on_invalid_map_property(true);
+    engine.set_max_expr_depths(64, 32);
+    engine.build_type::<synless::Keymap>();
+    engine.build_type::<synless::Layer>();
+    engine.build_type::<synless::KeyProg>();
+    engine.build_type::<synless::SynlessError>();
+    engine.build_type::<synless::Construct>();
+    engine.build_type::<synless::Language>();
+
+    let internals_ast = engine.compile_file(INTERNALS_MODULE_PATH.into()).unwrap();
+    let mut internals_mod =
+        rhai::Module::eval_ast_as_new(rhai::Scope::new(), &internals_ast, &engine).unwrap();
+    let base_ast = engine.compile_file(BASE_MODULE_PATH.into()).unwrap();
+    let mut base_mod =
+        rhai::Module::eval_ast_as_new(rhai::Scope::new(), &base_ast, &engine).unwrap();
+    Runtime::register_internal_methods(runtime.clone(), &mut internals_mod);
+    engine.register_static_module("synless_internals", internals_mod.into());
+    Runtime::register_external_methods(runtime.clone(), &mut base_mod);
+    engine.register_static_module("s", base_mod.into());
+    engine.set_strict_variables(true);
+
+    let init_ast = engine.compile_file(INIT_PATH.into()).unwrap();
+    let init_mod = rhai::Module::eval_ast_as_new(rhai::Scope::new(), &init_ast, &engine).unwrap();
+    engine.register_global_module(init_mod.into());
+    engine.run(TEST_LAYER).unwrap();
+
+    let main_ast = engine.compile_file(MAIN_PATH.into()).unwrap();
+    (runtime, engine, main_ast)
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// Move the cursor to the first node in the visible doc that matches `query`.
+fn goto(editor: &TestEditor, query: &str) {
+    editor.runtime().search(query).unwrap();
+    editor.press("n esc");
+}
+
+/// Delete the first node in the visible doc that matches `query`.
+fn delete_node(editor: &TestEditor, query: &str) {
+    goto(editor, query);
+    editor.press("del");
+}
+
+#[test]
+fn test_save_doc() {
+    let editor = TestEditor::new("save_doc");
+
+    // The trailing newline (or lack of one) is kept
+    for source in ["[1, 2]", "[1, 2]\n"] {
+        editor.open("file.json", source);
+        delete_node(&editor, "1");
+        assert!(editor.runtime().is_modified());
+        editor.press("C-s");
+        assert!(!editor.runtime().is_modified());
+        assert_eq!(editor.read("file.json"), source.replace("1, ", ""));
+        editor.runtime().close_doc().unwrap();
+    }
+}
+
+#[test]
+fn test_open_doc_twice() {
+    let editor = TestEditor::new("open_doc_twice");
+    fs::create_dir(editor.path("sub")).unwrap();
+    let path = editor.open("file.json", "[1]");
+
+    // The path is canonicalized, so the same file can't be opened again under another name
+    let alias = editor.path("sub/../file.json");
+    assert!(editor.runtime().open_doc(path_str(&alias)).is_err());
+    let docs = editor.open_docs();
+    let file_docs = docs.iter().filter(|doc| doc.ends_with("file.json"));
+    assert_eq!(file_docs.collect::<Vec<_>>(), vec![path_str(&path)]);
+}
+
+#[test]
+fn test_save_doc_as() {
+    let editor = TestEditor::new("save_doc_as");
+    fs::create_dir(editor.path("sub")).unwrap();
+    editor.open("other.json", "[2]");
+    let old_path = editor.open("old.json", "[1]");
+
+    // The new name is canonicalized, so it matches the already-open file
+    let other_path_alias = editor.path("sub/../other.json");
+    assert!(editor
+        .runtime()
+        .save_doc_as(path_str(&other_path_alias))
+        .is_err());
+    assert_eq!(editor.read("other.json"), "[2]");
+
+    // Saving as a new file renames the doc
+    let new_path_alias = editor.path("sub/../new.json");
+    editor
+        .runtime()
+        .save_doc_as(path_str(&new_path_alias))
+        .unwrap();
+    assert_eq!(editor.read("new.json"), "[1]");
+    let docs = editor.open_docs();
+    let new_doc = DocName::File(editor.path("new.json")).to_string();
+    let old_doc = DocName::File(old_path).to_string();
+    assert!(docs.contains(&new_doc));
+    assert!(!docs.contains(&old_doc));
+    assert!(!editor.runtime().is_modified());
+}
+
+#[test]
+fn test_recovery_file_names() {
+    let editor = TestEditor::new("recovery_file_names");
+    editor.open("a%/b.json", "[1]");
+    delete_node(&editor, "1");
+    editor.open("a/%b.json", "[2]");
+    delete_node(&editor, "2");
+    editor.runtime().save_recovery_files().unwrap();
+
+    // The full path is used, with `%` and `/` percent-encoded, so different files never share a
+    // recovery file
+    let dir = path_str(&editor.dir)
+        .replace('%', "%25")
+        .replace('/', "%2F");
+    assert_eq!(
+        editor.recovery_files(),
+        vec![
+            format!("{dir}%2Fa%25%2Fb.json.recovery"),
+            format!("{dir}%2Fa%2F%25b.json.recovery"),
+        ]
+    );
+}
+
+#[test]
+fn test_recovery_files() {
+    let mut editor = TestEditor::new("recovery_files");
+    let path = editor.open("file.json", "[1, 2]");
+    delete_node(&editor, "1");
+    editor.runtime().save_recovery_files().unwrap();
+    assert_eq!(editor.recovery_files().len(), 1);
+
+    // After a crash, the edit can be restored from the recovery file
+    editor.restart();
+    editor.runtime().open_doc(path_str(&path)).unwrap();
+    assert!(editor.runtime().has_recovery_file());
+    editor.runtime().restore_recovery_file().unwrap();
+    assert!(editor.runtime().is_modified());
+    // Saving deletes the recovery file
+    editor.press("C-s");
+    assert_eq!(editor.read("file.json"), "[2]");
+    assert!(editor.recovery_files().is_empty());
+
+    // So does closing the doc without saving
+    delete_node(&editor, "2");
+    editor.runtime().save_recovery_files().unwrap();
+    assert_eq!(editor.recovery_files().len(), 1);
+    editor.runtime().close_doc().unwrap();
+    assert!(editor.recovery_files().is_empty());
+    assert_eq!(editor.read("file.json"), "[2]");
+}
+
+#[test]
+fn test_recovery_files_after_undo() {
+    let editor = TestEditor::new("recovery_files_after_undo");
+    editor.open("file.json", "[1, 2]");
+    delete_node(&editor, "1");
+    editor.runtime().save_recovery_files().unwrap();
+    assert_eq!(editor.recovery_files().len(), 1);
+
+    // Once the edit is undone, the recovery file is stale, so the next autosave deletes it
+    editor.press("u");
+    assert!(!editor.runtime().is_modified());
+    editor.runtime().save_recovery_files().unwrap();
+    assert!(editor.recovery_files().is_empty());
+}
+
+#[test]
+fn test_autosave_without_idling() {
+    let mut settings = Settings::default();
+    settings.autosave_interval = Duration::ZERO;
+    let editor = TestEditor::with_settings("autosave", settings);
+    editor.open("file.json", "[1, 2]");
+
+    // There's always another key ready, so the frontend never times out
+    delete_node(&editor, "1");
+    let recovery_files = editor.recovery_files();
+    assert_eq!(recovery_files.len(), 1);
+    let recovery_path = editor.settings.recovery_dir.join(&recovery_files[0]);
+    assert_eq!(fs::read_to_string(recovery_path).unwrap(), "[2]");
+}
+
+#[test]
+fn test_record_macro() {
+    let editor = TestEditor::new("record_macro");
+    editor.open("file.json", "[1, 2, 3]");
+
+    // Neither the key that stops the recording, nor the keys that start it, are part of the macro
+    editor.press("q a up enter j k q");
+    assert!(!editor.runtime().is_recording_macro());
+    assert_eq!(editor.runtime().get_macro('a').unwrap(), "j k");
+}
+
+#[test]
+fn test_replay_macro() {
+    let editor = TestEditor::new("replay_macro");
+    editor.open("file.json", "[1, 2, 3, 4, 5]");
+    goto(&editor, "1");
+
+    // The replayed keys are handled like real ones, and the whole replay is one undo group
+    editor.runtime().set_macro('a', "del del").unwrap();
+    editor.press("@ enter C-s");
+    assert_eq!(editor.read("file.json"), "[3, 4, 5]");
+    editor.press("u C-s");
+    assert_eq!(editor.read("file.json"), "[1, 2, 3, 4, 5]");
+
+    // A macro can't replay itself
+    editor.runtime().set_macro('a', "del @ enter").unwrap();
+    goto(&editor, "1");
+    editor.press("@ enter C-s");
+    assert_eq!(editor.read("file.json"), "[2, 3, 4, 5]");
+}
+
+#[test]
+fn test_replay_macro_with_jumps() {
+    let editor = TestEditor::new("replay_macro_with_jumps");
+    editor.open("file.json", "[1, 0, 2, 0, 3]");
+    editor.runtime().search("0").unwrap();
+
+    // The replay stops when the third search fails, so its `del` never happens
+    editor.runtime().set_macro('a', "n del").unwrap();
+    editor.runtime().replay_macro('a', 3).unwrap();
+    editor.press("C-s");
+    assert_eq!(editor.read("file.json"), "[1, 2, 3]");
+
+    // Jumping to the search results doesn't split the replay into several undo groups
+    editor.press("u C-s");
+    assert_eq!(editor.read("file.json"), "[1, 0, 2, 0, 3]");
+}
+
+#[test]
+fn test_repeat_last_edit() {
+    let editor = TestEditor::new("repeat_last_edit");
+    editor.open("file.json", "[1, 2, 3]");
+    assert!(editor.runtime().repeat_last_edit().is_err());
+
+    // Moving the cursor isn't an edit, so it isn't what gets repeated
+    goto(&editor, "1");
+    editor.press("del j . C-s");
+    assert_eq!(editor.read("file.json"), "[2]");
+
+    // An edit selected from a menu is repeated with the same selection, without the menu
+    editor.press("i x k . C-s");
+    assert_eq!(editor.read("file.json"), "[2, null, null]");
+    editor.open("wrap.json", "[1, 2]");
+    goto(&editor, "1");
+    editor.press("e a");
+    goto(&editor, "2");
+    editor.press(". C-s");
+    assert_eq!(editor.read("wrap.json"), "[[1], [2]]");
+}
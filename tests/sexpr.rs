@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::Path;
+use synless::{parsing::JsonParser, DocName, Engine, Settings};
+
+const JSON_PATH: &str = "data/json_lang.ron";
+
+fn make_engine() -> (Engine, String) {
+    let mut engine = Engine::new(Settings::default());
+    let json_lang_ron = fs::read_to_string(JSON_PATH).unwrap();
+    let language_name = engine
+        .load_language_ron(Path::new(JSON_PATH), &json_lang_ron)
+        .unwrap();
+    engine.add_parser(&language_name, JsonParser);
+    (engine, language_name)
+}
+
+#[test]
+fn test_sexpr_round_trip() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let sexpr = "(Root (Array (True) (Null) (String \"say \\\"hi\\\"\") (Object (ObjectPair (Key \"k\") ($hole)))))";
+    engine
+        .load_doc_from_sexpr(doc_name.clone(), &language_name, sexpr)
+        .unwrap();
+    assert_eq!(engine.print_sexpr(&doc_name).unwrap(), sexpr);
+}
+
+#[test]
+fn test_sexpr_from_source() {
+    let (mut engine, language_name) = make_engine();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    let source = "[1, \"two\", {\"three\": false}]";
+    engine
+        .load_doc_from_source(doc_name.clone(), &language_name, source)
+        .unwrap();
+    let sexpr = engine.print_sexpr(&doc_name).unwrap();
+    assert_eq!(
+        sexpr,
+        "(Root (Array (Number \"1\") (String \"two\") (Object (ObjectPair (Key \"three\") (False)))))"
+    );
+
+    let copy_name = DocName::Auxilliary("<copy>".to_owned());
+    engine
+        .load_doc_from_sexpr(copy_name.clone(), &language_name, &sexpr)
+        .unwrap();
+    assert_eq!(engine.print_source(&copy_name).unwrap(), source);
+}
+
+#[test]
+fn test_sexpr_errors() {
+    let (mut engine, language_name) = make_engine();
+
+    let mut load = |sexpr: &str| {
+        let doc_name = DocName::Auxilliary(sexpr.to_owned());
+        engine
+            .load_doc_from_sexpr(doc_name, &language_name, sexpr)
+            .unwrap_err()
+            .message
+    };
+
+    assert_eq!(
+        load("(Root\n  (Object (Null)))"),
+        "In auxilliary:(Root\n  (Object (Null))) at 2:11: 'Null' is not allowed as a child of 'Object'"
+    );
+    assert_eq!(
+        load("(Root (True) (False))"),
+        "In auxilliary:(Root (True) (False)) at 1:1: 'Root' must have exactly 1 children, but has 2"
+    );
+    assert_eq!(
+        load("(Root (Nope))"),
+        "In auxilliary:(Root (Nope)) at 1:8: Construct 'Nope' does not exist in language 'json'"
+    );
+    assert_eq!(
+        load("(Root (String \"a\" \"b\"))"),
+        "In auxilliary:(Root (String \"a\" \"b\")) at 1:19: Expected ')'"
+    );
+}
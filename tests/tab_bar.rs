@@ -0,0 +1,45 @@
+use std::fs;
+use std::path::Path;
+use synless::{DocName, Engine, Node, Settings};
+
+const TAB_BAR_PATH: &str = "data/tab_bar_lang.ron";
+const TAB_BAR_NOTATION_NAME: &str = "DefaultDisplay";
+
+#[test]
+fn test_tab_bar_lang() {
+    let mut engine = Engine::new(Settings::default());
+
+    let tab_bar_lang_ron = fs::read_to_string(TAB_BAR_PATH).unwrap();
+    let language_name = engine
+        .load_language_ron(Path::new(TAB_BAR_PATH), &tab_bar_lang_ron)
+        .unwrap();
+    engine
+        .set_source_notation(&language_name, TAB_BAR_NOTATION_NAME)
+        .unwrap();
+
+    let doc_name = DocName::Auxilliary("<testing>".to_owned());
+    engine.add_empty_doc(&doc_name, &language_name).unwrap();
+
+    let mut cursor = engine.get_doc(&doc_name).unwrap().cursor();
+    let s = engine.raw_storage_mut();
+    let lang = s.language(&language_name).unwrap();
+    let c_tab = lang.construct(s, "Tab").unwrap();
+    let c_modified = lang.construct(s, "Modified").unwrap();
+    let c_visible = lang.construct(s, "Visible").unwrap();
+
+    let tab = Node::with_text(s, c_tab, "a.json".to_owned()).unwrap();
+    cursor.insert(s, tab).unwrap();
+
+    let tab = Node::with_text(s, c_tab, "b.json".to_owned()).unwrap();
+    let modified = Node::with_children(s, c_modified, [tab]).unwrap();
+    let visible = Node::with_children(s, c_visible, [modified]).unwrap();
+    cursor.insert(s, visible).unwrap();
+
+    let tab = Node::with_text(s, c_tab, "c.json".to_owned()).unwrap();
+    let modified = Node::with_children(s, c_modified, [tab]).unwrap();
+    cursor.insert(s, modified).unwrap();
+
+    let output = engine.print_source(&doc_name).unwrap();
+    let expected = "a.json | b.json* | c.json*";
+    assert_eq!(output, expected);
+}
@@ -13,6 +13,7 @@ fn urllang() -> LanguageSpec {
     LanguageSpec {
         name: "urllang".to_owned(),
         file_extensions: Vec::new(),
+        trim_blank_lines: false,
         grammar: GrammarSpec {
             constructs: vec![
                 ConstructSpec {
@@ -136,3 +137,33 @@ fn test_doc_ref() {
     let expected = "example.com?\n    param1=val1\n    &param2=val2\n    &done";
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn test_retyped_copy() {
+    let mut s = Storage::new();
+    s.add_language(urllang()).unwrap();
+    let lang = s.language("urllang").unwrap();
+    let c_params = lang.construct(&s, "Params").unwrap();
+    let c_equals = lang.construct(&s, "Equals").unwrap();
+
+    // Fixed -> listy keeps every child
+    let key = node_with_text(&mut s, "urllang", "String", "key");
+    let val = node_with_text(&mut s, "urllang", "String", "val");
+    let eq = node_with_children(&mut s, "urllang", "Equals", [key, val]);
+    let (params, num_dropped) = eq.retyped_copy(&mut s, c_params);
+    assert_eq!(num_dropped, 0);
+    assert_eq!(params.num_children(&s), Some(2));
+    let first = params.first_child(&s).unwrap();
+    assert_eq!(first.text(&s).unwrap().as_str(), "key");
+    assert_ne!(first, key);
+
+    // Listy -> fixed drops children that don't fit, and fills the rest with holes
+    let done = node_with_text(&mut s, "urllang", "String", "done");
+    let params = node_with_children(&mut s, "urllang", "Params", [eq, done]);
+    let (eq_copy, num_dropped) = params.retyped_copy(&mut s, c_equals);
+    assert_eq!(num_dropped, 1);
+    let first = eq_copy.first_child(&s).unwrap();
+    assert_eq!(first.text(&s).unwrap().as_str(), "done");
+    let second = eq_copy.last_child(&s).unwrap();
+    assert!(second.construct(&s).is_hole(&s));
+}